@@ -0,0 +1,23 @@
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::core::error::Error;
+
+///
+/// Domain event emitted by a service, e.g. once an organization has been created.
+///
+pub trait Event: Serialize {
+    ///
+    /// Routing key the event is published with, e.g. `org.created`.
+    ///
+    fn routing_key(&self) -> String;
+}
+
+///
+/// Publishes events without waiting for any reply, unlike the `InputConsumer`.
+///
+#[async_trait]
+pub trait EventPublisher {
+    async fn publish<EventType: Event + Send + Sync>(&self, event: &EventType)
+        -> Result<(), Error>;
+}
@@ -1,31 +1,87 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::api::server::input::action::Action;
-use crate::api::server::input::executor::Executor;
+use crate::api::server::input::action::{versioned_action_key, Action, ACTION_VERSION_SEPARATOR};
 use crate::api::server::input::input::Input;
 use crate::api::server::input::input_data::InputData;
 use crate::api::server::input::input_plugin::InputPlugin;
 use crate::api::server::input::replier::Replier;
 use crate::api::shared::request::Request;
 use crate::api::shared::request_header::RequestHeader;
+#[cfg(test)]
+use crate::api::shared::response::{decode_response, RESPONSE_VERSION};
+use crate::api::shared::response::{encode_response, Response, ResponseFormat};
 use async_channel::Sender;
 use async_trait::async_trait;
 use log::{info, warn};
 use serde_json::{json, Value};
-use tokio::sync::RwLock;
-use tokio::task::JoinHandle;
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
 use tokio::time::{sleep, timeout};
 use tokio_util::sync::CancellationToken;
+use tracing::{field, info_span, Instrument, Span};
+
+use crate::core::error::{Error, ErrorKind};
+use crate::core::metrics::{metrics, UNKNOWN_ACTION_LABEL};
+
+pub const DEFAULT_MAX_IN_FLIGHT_REQUESTS: usize = 32usize;
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(10u64);
 
-use crate::core::error::Error;
+///
+/// Header extra which is present within the header handed to the plugins' response hooks when
+/// the response was produced by the action's executor, instead of by a plugin or the dispatch.
+///
+pub const ACTION_EXECUTED_EXTRA: &str = "action_executed";
+
+const MAX_CLOSE_ACTIONS: usize = 5usize;
+const MAX_CLOSE_ACTION_DISTANCE: usize = 3usize;
 
 pub struct Dispatch<InputImpl: 'static + Input + Send, LogicRequestType: 'static + Send> {
     inputs: Vec<InputImpl>,
     actions: Arc<HashMap<String, Action<LogicRequestType>>>,
     sender: Sender<LogicRequestType>,
     plugins: Arc<Vec<Arc<dyn InputPlugin + Send + Sync>>>,
+    max_in_flight_requests: usize,
+    drain_timeout: Duration,
+    response_format: ResponseFormat,
+}
+
+#[derive(Copy, Clone)]
+struct InputSettings {
+    max_in_flight_requests: usize,
+    drain_timeout: Duration,
+    response_format: ResponseFormat,
+}
+
+///
+/// Outcome of the requests which were still in flight when an input was stopped.
+///
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ShutdownReport {
+    drained: usize,
+    abandoned: usize,
+}
+
+impl ShutdownReport {
+    pub fn new(drained: usize, abandoned: usize) -> ShutdownReport {
+        ShutdownReport { drained, abandoned }
+    }
+
+    pub fn drained(&self) -> usize {
+        self.drained
+    }
+
+    pub fn abandoned(&self) -> usize {
+        self.abandoned
+    }
+
+    pub fn merge(self, other: ShutdownReport) -> ShutdownReport {
+        ShutdownReport::new(
+            self.drained + other.drained,
+            self.abandoned + other.abandoned,
+        )
+    }
 }
 
 impl<InputImpl: 'static + Input + Send, LogicRequestType: 'static + Send>
@@ -42,11 +98,58 @@ impl<InputImpl: 'static + Input + Send, LogicRequestType: 'static + Send>
             actions: Arc::new(actions),
             sender,
             plugins: Arc::new(plugins),
+            max_in_flight_requests: DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            response_format: ResponseFormat::default(),
         }
     }
 
-    pub async fn run(self, cancellation_token: CancellationToken) -> Vec<JoinHandle<()>> {
+    ///
+    /// Maximum amount of requests each input handles concurrently. Once the limit is reached,
+    /// the input stops receiving until one of the in-flight requests has been replied.
+    ///
+    pub fn with_max_in_flight_requests(
+        mut self,
+        max_in_flight_requests: usize,
+    ) -> Dispatch<InputImpl, LogicRequestType> {
+        self.max_in_flight_requests = max_in_flight_requests.max(1usize);
+        self
+    }
+
+    ///
+    /// Time given, once the cancellation token is cancelled, to the in-flight requests of each
+    /// input before they are abandoned.
+    ///
+    pub fn with_drain_timeout(
+        mut self,
+        drain_timeout: Duration,
+    ) -> Dispatch<InputImpl, LogicRequestType> {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    ///
+    /// Layout of the replies, `ResponseFormat::Legacy` keeps replying with the serialized action
+    /// result for the clients which have not migrated yet to the `Response` envelope.
+    ///
+    pub fn with_response_format(
+        mut self,
+        response_format: ResponseFormat,
+    ) -> Dispatch<InputImpl, LogicRequestType> {
+        self.response_format = response_format;
+        self
+    }
+
+    pub async fn run(
+        self,
+        cancellation_token: CancellationToken,
+    ) -> Vec<JoinHandle<ShutdownReport>> {
         let mut api_handles = Vec::new();
+        let input_settings = InputSettings {
+            max_in_flight_requests: self.max_in_flight_requests,
+            drain_timeout: self.drain_timeout,
+            response_format: self.response_format,
+        };
 
         for input in self.inputs {
             let actions_pointer: Arc<HashMap<String, Action<LogicRequestType>>> =
@@ -59,6 +162,7 @@ impl<InputImpl: 'static + Input + Send, LogicRequestType: 'static + Send>
                 actions_pointer,
                 logic_request_sender,
                 plugins_pointer,
+                input_settings,
                 cancellation_token.clone(),
             )));
         }
@@ -67,6 +171,75 @@ impl<InputImpl: 'static + Input + Send, LogicRequestType: 'static + Send>
     }
 }
 
+///
+/// Key of the action requested through the header. Versions can be requested either within the
+/// action, e.g. `create_org@v2`, or through the header's version; otherwise the key routes to the
+/// action's default version.
+///
+pub(crate) fn requested_action_key(header: &RequestHeader) -> String {
+    match header.version() {
+        Some(version) if !header.action().contains(ACTION_VERSION_SEPARATOR) => {
+            versioned_action_key(header.action(), version)
+        }
+        _ => header.action().to_string(),
+    }
+}
+
+///
+/// Requests without version are resolved to the default version of their action, so that the
+/// plugins look up the limits and schemas of the version which is executed.
+///
+fn resolve_default_version<LogicRequestType>(
+    header: &mut RequestHeader,
+    actions: &Arc<HashMap<String, Action<LogicRequestType>>>,
+) {
+    if header.version().is_some() || header.action().contains(ACTION_VERSION_SEPARATOR) {
+        return;
+    }
+
+    let default_version = match actions
+        .get(header.action())
+        .and_then(|action| action.version())
+    {
+        Some(default_version) => default_version.to_string(),
+        None => return,
+    };
+
+    header.set_version(default_version);
+}
+
+///
+/// Label of the action within the metrics, unknown actions share the same label.
+///
+fn action_metrics_label<'a, LogicRequestType>(
+    action: &'a str,
+    actions: &Arc<HashMap<String, Action<LogicRequestType>>>,
+) -> &'a str {
+    if actions.contains_key(action) {
+        action
+    } else {
+        UNKNOWN_ACTION_LABEL
+    }
+}
+
+///
+/// Records the outcome of a request within the metrics and the request's span.
+///
+fn record_request_outcome(action: &str, result: &Result<Value, Error>, elapsed: Duration) {
+    metrics().record_api_request(action, result, elapsed);
+
+    let span = Span::current();
+    match result {
+        Ok(_) => {
+            span.record("status", "ok");
+        }
+        Err(error) => {
+            span.record("status", "error");
+            span.record("error_kind", format!("{:?}", error.kind()).as_str());
+        }
+    }
+}
+
 fn get_filtered_out_plugins_for_action<LogicRequestType>(
     action: &str,
     actions: &Arc<HashMap<String, Action<LogicRequestType>>>,
@@ -81,23 +254,282 @@ async fn handle_input_data<LogicRequestType: 'static + Send>(
     input_data: InputData,
     actions: &Arc<HashMap<String, Action<LogicRequestType>>>,
     sender: Sender<LogicRequestType>,
+    plugins: &[Arc<dyn InputPlugin + Send + Sync>],
+    received_at: Instant,
+    response_format: ResponseFormat,
 ) {
-    let action = input_data.request.header().action();
+    let mut header = input_data.request.header().clone();
+    let action = requested_action_key(&header);
+    let warnings = get_warnings_for_action(&action, actions);
+    let mut action_result = match input_data.response {
+        Some(response) => response,
+        None => {
+            if actions.contains_key(&action) {
+                header.add_extra(ACTION_EXECUTED_EXTRA.to_string(), "true".to_string());
+            }
+
+            execute_action(input_data.request, actions, sender).await
+        }
+    };
+
+    // response hooks run in the reverse order of the request ones, so the first plugin to see
+    // the request is the last one to see its response
+    for plugin in plugins.iter().rev() {
+        action_result = plugin
+            .handle_response(&header, received_at.elapsed(), action_result)
+            .await;
+    }
+
+    let replier: Replier = input_data.replier;
+    record_request_outcome(
+        action_metrics_label(&action, actions),
+        &action_result,
+        received_at.elapsed(),
+    );
+
+    let reply = build_reply(
+        action_result,
+        &header,
+        warnings,
+        received_at.elapsed(),
+        response_format,
+    );
 
+    if let Err(error) = replier(reply).await {
+        warn!("failed to reply with action_result: {}", error);
+    }
+}
+
+fn get_warnings_for_action<LogicRequestType>(
+    action: &str,
+    actions: &Arc<HashMap<String, Action<LogicRequestType>>>,
+) -> Vec<String> {
     match actions.get(action) {
-        Some(action) => {
-            let executor = action.executor();
-            let action_result = executor(input_data.request, sender).await;
+        Some(action) => match action.deprecation() {
+            Some(deprecation) => vec![format!(
+                "action '{}' is deprecated: {}",
+                action.key(),
+                deprecation
+            )],
+            None => Vec::new(),
+        },
+        None => Vec::new(),
+    }
+}
 
-            let replier: Replier = input_data.replier;
-            if let Err(error) = replier(json!(action_result)).await {
-                warn!("failed to reply with action_result: {}", error);
-            }
-        }
+fn build_reply(
+    action_result: Result<Value, Error>,
+    header: &RequestHeader,
+    warnings: Vec<String>,
+    processing_time: Duration,
+    response_format: ResponseFormat,
+) -> Value {
+    let response = Response::new(action_result)
+        .with_request_id(header.id().map(|id| id.to_string()))
+        .with_processing_time(processing_time)
+        .with_warnings(warnings);
+
+    encode_response(response, response_format)
+}
+
+async fn execute_action<LogicRequestType: 'static + Send>(
+    request: Request,
+    actions: &Arc<HashMap<String, Action<LogicRequestType>>>,
+    sender: Sender<LogicRequestType>,
+) -> Result<Value, Error> {
+    let action = requested_action_key(request.header());
+
+    match actions.get(&action) {
+        Some(action) => execute_within_limits(action, request, sender).await,
         None => {
             info!("unknown action received: {}", action);
+
+            let close_actions = find_close_actions(&action, actions);
+            Err(unknown_action_error(&action, close_actions))
+        }
+    }
+}
+
+///
+/// Runs the action's executor enforcing the action's concurrency limit and timeout.
+///
+async fn execute_within_limits<LogicRequestType: 'static + Send>(
+    action: &Action<LogicRequestType>,
+    request: Request,
+    sender: Sender<LogicRequestType>,
+) -> Result<Value, Error> {
+    let _permit = match action.concurrency_limiter() {
+        Some(concurrency_limiter) => match concurrency_limiter.try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                return Err(Error::new(
+                    ErrorKind::OverloadedError,
+                    format!(
+                        "action '{}' reached its limit of {} concurrent executions",
+                        action.key(),
+                        action.max_concurrency().unwrap_or_default()
+                    ),
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let executor = action.executor();
+
+    match action.timeout() {
+        Some(timeout_after) => match timeout(timeout_after, executor(request, sender)).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::new(
+                ErrorKind::TimeoutError,
+                format!(
+                    "action '{}' timed out after {} milliseconds",
+                    action.key(),
+                    timeout_after.as_millis()
+                ),
+            )),
+        },
+        None => executor(request, sender).await,
+    }
+}
+
+fn unknown_action_error(action: &str, close_actions: Vec<String>) -> Error {
+    let message = if close_actions.is_empty() {
+        format!("unknown action '{}'", action)
+    } else {
+        format!(
+            "unknown action '{}', did you mean: '{}'?",
+            action,
+            close_actions.join("', '")
+        )
+    };
+
+    Error::new(ErrorKind::UnknownActionError, message).with_details(json!({
+        "action": action,
+        "close_actions": close_actions,
+    }))
+}
+
+///
+/// Registered actions which are within a small edit distance of the requested one, closest first.
+///
+fn find_close_actions<LogicRequestType>(
+    action: &str,
+    actions: &Arc<HashMap<String, Action<LogicRequestType>>>,
+) -> Vec<String> {
+    let mut close_actions: Vec<(usize, &String)> = actions
+        .keys()
+        .map(|registered_action| (edit_distance(action, registered_action), registered_action))
+        .filter(|(distance, _)| *distance <= MAX_CLOSE_ACTION_DISTANCE)
+        .collect();
+
+    close_actions.sort();
+
+    close_actions
+        .into_iter()
+        .take(MAX_CLOSE_ACTIONS)
+        .map(|(_, registered_action)| registered_action.clone())
+        .collect()
+}
+
+fn edit_distance(first: &str, second: &str) -> usize {
+    let second: Vec<char> = second.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=second.len()).collect();
+
+    for (first_index, first_char) in first.chars().enumerate() {
+        let mut current_row: Vec<usize> = vec![first_index + 1];
+
+        for (second_index, second_char) in second.iter().enumerate() {
+            let substitution_cost = usize::from(first_char != *second_char);
+
+            current_row.push(
+                (previous_row[second_index] + substitution_cost)
+                    .min(previous_row[second_index + 1] + 1)
+                    .min(current_row[second_index] + 1),
+            );
         }
+
+        previous_row = current_row;
+    }
+
+    previous_row[second.len()]
+}
+
+async fn handle_request<LogicRequestType: 'static + Send>(
+    mut input_data: InputData,
+    received_at: Instant,
+    actions_pointer: Arc<HashMap<String, Action<LogicRequestType>>>,
+    logic_request_sender: Sender<LogicRequestType>,
+    plugins_pointer: Arc<Vec<Arc<dyn InputPlugin + Send + Sync>>>,
+    response_format: ResponseFormat,
+) {
+    // only the dispatch tells the plugins whether the action was executed
+    input_data
+        .request
+        .mut_header()
+        .remove_extra(ACTION_EXECUTED_EXTRA);
+    resolve_default_version(input_data.request.mut_header(), &actions_pointer);
+    let action = requested_action_key(input_data.request.header());
+    let filtered_out_plugins =
+        get_filtered_out_plugins_for_action::<LogicRequestType>(&action, &actions_pointer);
+
+    let plugins: Vec<Arc<dyn InputPlugin + Send + Sync>> = plugins_pointer
+        .iter()
+        .filter(|plugin| !filtered_out_plugins.contains(&plugin.id().to_string()))
+        .cloned()
+        .collect();
+
+    for (plugin_index, plugin) in plugins.iter().enumerate() {
+        input_data = match plugin.handle_input_data(input_data).await {
+            Ok(input_data) => input_data,
+            Err((input_data, error)) => {
+                warn!("plugin failed to handle input data: {}", error);
+                metrics().record_plugin_rejection(plugin.id(), &error);
+
+                // the plugins which already handled the request see the rejection as its response
+                let mut result = Err(error);
+                for plugin in plugins[..plugin_index].iter().rev() {
+                    result = plugin
+                        .handle_response(input_data.request.header(), received_at.elapsed(), result)
+                        .await;
+                }
+
+                record_request_outcome(
+                    action_metrics_label(&action, &actions_pointer),
+                    &result,
+                    received_at.elapsed(),
+                );
+
+                let reply = build_reply(
+                    result,
+                    input_data.request.header(),
+                    Vec::new(),
+                    received_at.elapsed(),
+                    response_format,
+                );
+                let replier = input_data.replier;
+
+                match replier(reply).await {
+                    Ok(_) => (),
+                    Err(error) => {
+                        warn!("failed to reply when plugin failed: {}", error)
+                    }
+                }
+
+                return;
+            }
+        };
     }
+
+    handle_input_data::<LogicRequestType>(
+        input_data,
+        &actions_pointer,
+        logic_request_sender,
+        &plugins,
+        received_at,
+        response_format,
+    )
+    .await;
 }
 
 async fn run_dispatch_input<InputImpl: 'static + Input + Send, LogicRequestType: 'static + Send>(
@@ -105,78 +537,133 @@ async fn run_dispatch_input<InputImpl: 'static + Input + Send, LogicRequestType:
     actions_pointer: Arc<HashMap<String, Action<LogicRequestType>>>,
     logic_request_sender: Sender<LogicRequestType>,
     plugins_pointer: Arc<Vec<Arc<dyn InputPlugin + Send + Sync>>>,
+    input_settings: InputSettings,
     cancellation_token: CancellationToken,
-) {
+) -> ShutdownReport {
+    let in_flight_semaphore = Arc::new(Semaphore::new(input_settings.max_in_flight_requests));
+    let mut in_flight_requests: JoinSet<()> = JoinSet::new();
+
     loop {
-        if cancellation_token.is_cancelled() {
-            info!("cancellation token is cancelled, api dispatch is stopping");
+        while in_flight_requests.try_join_next().is_some() {}
 
-            break;
-        }
+        let result = tokio::select! {
+            biased;
+            _ = cancellation_token.cancelled() => {
+                info!("cancellation token is cancelled, api dispatch is stopping");
 
-        let result = input.receive().await;
+                break;
+            }
+            // inputs are cancel safe, so the request being received is not lost
+            result = receive_with_permit(&mut input, &in_flight_semaphore) => result,
+        };
 
         match result {
-            Ok(mut input_data) => {
-                if plugins_pointer.len() == 0 {
-                    handle_input_data::<LogicRequestType>(
+            Ok((input_data, in_flight_permit)) => {
+                let received_at = Instant::now();
+                let actions_pointer = actions_pointer.clone();
+                let logic_request_sender = logic_request_sender.clone();
+                let plugins_pointer = plugins_pointer.clone();
+
+                let span = info_span!(
+                    "api_request",
+                    action = %requested_action_key(input_data.request.header()),
+                    request_id = input_data.request.header().id().unwrap_or_default(),
+                    status = field::Empty,
+                    error_kind = field::Empty,
+                );
+
+                metrics().add_api_in_flight_requests(1i64);
+
+                in_flight_requests.spawn(async move {
+                    handle_request::<LogicRequestType>(
                         input_data,
-                        &actions_pointer,
-                        logic_request_sender.clone(),
+                        received_at,
+                        actions_pointer,
+                        logic_request_sender,
+                        plugins_pointer,
+                        input_settings.response_format,
                     )
+                    .instrument(span)
                     .await;
-                } else {
-                    let filtered_out_plugins =
-                        get_filtered_out_plugins_for_action::<LogicRequestType>(
-                            input_data.request.header().action(),
-                            &actions_pointer,
-                        );
-
-                    for (index, plugin) in plugins_pointer.as_slice().iter().enumerate() {
-                        if !filtered_out_plugins.contains(&plugin.id().to_string()) {
-                            input_data = match plugin.handle_input_data(input_data).await {
-                                Ok(input_data) => input_data,
-                                Err((input_data, error)) => {
-                                    let replier = input_data.replier;
-
-                                    let error_value = match serde_json::to_value(error.clone()) {
-                                        Ok(error_value) => error_value,
-                                        Err(error) => {
-                                            json!(format!("failed to process request: {}", error))
-                                        }
-                                    };
-
-                                    match replier(error_value).await {
-                                        Ok(_) => (),
-                                        Err(error) => {
-                                            warn!("failed to reply when plugin failed: {}", error)
-                                        }
-                                    }
-
-                                    warn!("plugin failed to handle input data: {}", error);
-                                    break;
-                                }
-                            };
-                        }
-
-                        if index == plugins_pointer.len() - 1 {
-                            handle_input_data::<LogicRequestType>(
-                                input_data,
-                                &actions_pointer,
-                                logic_request_sender.clone(),
-                            )
-                            .await;
-
-                            break;
-                        }
-                    }
-                }
+
+                    metrics().add_api_in_flight_requests(-1i64);
+                    drop(in_flight_permit);
+                });
             }
             Err(error) => {
                 warn!("failed to receive input: {}", error);
             }
         }
     }
+
+    let shutdown_report =
+        drain_in_flight_requests(in_flight_requests, input_settings.drain_timeout).await;
+
+    if let Err(error) = input.stop().await {
+        warn!("failed to stop input: {}", error);
+    }
+
+    info!(
+        "api dispatch input stopped, drained requests: {}, abandoned requests: {}",
+        shutdown_report.drained(),
+        shutdown_report.abandoned()
+    );
+
+    shutdown_report
+}
+
+async fn receive_with_permit<InputImpl: Input>(
+    input: &mut InputImpl,
+    in_flight_semaphore: &Arc<Semaphore>,
+) -> Result<(InputData, OwnedSemaphorePermit), Error> {
+    // the permit is acquired before receiving so the input does not take more requests
+    // than it is able to handle concurrently
+    let in_flight_permit = match in_flight_semaphore.clone().acquire_owned().await {
+        Ok(in_flight_permit) => in_flight_permit,
+        Err(error) => {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to acquire in-flight request permit: {}", error),
+            ))
+        }
+    };
+
+    let input_data = input.receive().await?;
+
+    Ok((input_data, in_flight_permit))
+}
+
+///
+/// Waits for the in-flight requests to be replied, aborting the ones still running once the
+/// drain timeout is over.
+///
+async fn drain_in_flight_requests(
+    mut in_flight_requests: JoinSet<()>,
+    drain_timeout: Duration,
+) -> ShutdownReport {
+    let mut drained: usize = 0usize;
+
+    let drain_result = timeout(drain_timeout, async {
+        while let Some(result) = in_flight_requests.join_next().await {
+            match result {
+                Ok(_) => drained += 1,
+                Err(error) => warn!("in-flight request failed while draining: {}", error),
+            }
+        }
+    })
+    .await;
+
+    let abandoned = match drain_result {
+        Ok(_) => 0usize,
+        Err(_) => {
+            let abandoned = in_flight_requests.len();
+            in_flight_requests.abort_all();
+
+            abandoned
+        }
+    };
+
+    ShutdownReport::new(drained, abandoned)
 }
 
 #[cfg(test)]
@@ -205,13 +692,13 @@ impl Input for InputTimedImpl {
             .await
             .expect("failed to send empty message");
 
-        Ok(InputData {
-            request: Request::new(
+        Ok(InputData::new(
+            Request::new(
                 RequestHeader::new("".to_string(), "".to_string()),
                 Value::Null,
             ),
-            replier: Arc::new(move |value: Value| Box::pin(async { Ok(()) })),
-        })
+            Arc::new(move |_value: Value| Box::pin(async { Ok(()) })),
+        ))
     }
 }
 
@@ -272,13 +759,13 @@ impl Input for InputDummyImpl {
             RequestHeader::new("".to_string(), "".to_string()),
             Value::Null,
         );
-        let replier: Replier = Arc::new(move |value| Box::pin(async { Ok(()) }));
+        let replier: Replier = Arc::new(move |_value| Box::pin(async { Ok(()) }));
 
         if !(*self.has_message_been_sent.try_read().unwrap()) {
             *self.has_message_been_sent.try_write().unwrap() = true;
         }
 
-        Ok(InputData { request, replier })
+        Ok(InputData::new(request, replier))
     }
 }
 
@@ -340,3 +827,593 @@ pub async fn execute_specified_plugins_for_each_input() {
 
     assert_eq!(EXPECTED_SUM, sum);
 }
+
+#[cfg(test)]
+pub struct InputQueuedImpl {
+    pending_actions: Vec<String>,
+    reply_sender: Option<tokio::sync::mpsc::Sender<Value>>,
+}
+
+#[cfg(test)]
+impl InputQueuedImpl {
+    pub fn new(pending_actions: Vec<String>) -> InputQueuedImpl {
+        InputQueuedImpl {
+            pending_actions,
+            reply_sender: None,
+        }
+    }
+
+    pub fn with_reply_sender(mut self, reply_sender: tokio::sync::mpsc::Sender<Value>) -> Self {
+        self.reply_sender = Some(reply_sender);
+        self
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Input for InputQueuedImpl {
+    async fn receive(&mut self) -> Result<InputData, Error> {
+        let action = match self.pending_actions.pop() {
+            Some(action) => action,
+            None => loop {
+                sleep(Duration::MAX).await;
+            },
+        };
+
+        let reply_sender = self.reply_sender.clone();
+
+        Ok(InputData::new(
+            Request::new(RequestHeader::new(action, "".to_string()), Value::Null),
+            Arc::new(move |value| {
+                let reply_sender = reply_sender.clone();
+
+                Box::pin(async move {
+                    if let Some(reply_sender) = reply_sender {
+                        reply_sender.send(value).await.unwrap();
+                    }
+
+                    Ok(())
+                })
+            }),
+        ))
+    }
+}
+
+#[cfg(test)]
+fn slow_action(
+    sleep_duration: Duration,
+    sender: tokio::sync::mpsc::Sender<()>,
+) -> HashMap<String, Action<LogicRequest>> {
+    let slow_action: Action<LogicRequest> = Action::new(
+        "slow".to_string(),
+        Arc::new(move |_request, _logic_request_sender| {
+            let sender = sender.clone();
+
+            Box::pin(async move {
+                sleep(sleep_duration).await;
+                sender.send(()).await.expect("failed to send empty message");
+
+                Ok(Value::Null)
+            })
+        }),
+        Vec::new(),
+    );
+
+    HashMap::from([("slow".to_string(), slow_action)])
+}
+
+#[tokio::test]
+pub async fn handle_multiple_requests_of_same_input_concurrently() {
+    let sleep_duration: Duration = Duration::from_millis(500u64);
+    let max_execution_duration: Duration = Duration::from_millis(900u64);
+    let expected_requests: u8 = 3;
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<()>(1024usize);
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+    let inputs: Vec<InputQueuedImpl> = vec![InputQueuedImpl::new(vec![
+        "slow".to_string();
+        expected_requests as usize
+    ])];
+    let dispatch: Dispatch<InputQueuedImpl, LogicRequest> = Dispatch::new(
+        inputs,
+        slow_action(sleep_duration, sender),
+        logic_request_sender,
+        vec![],
+    );
+
+    tokio::spawn(dispatch.run(CancellationToken::new()));
+
+    timeout(max_execution_duration, async move {
+        for _ in 0..expected_requests {
+            receiver
+                .recv()
+                .await
+                .expect("failed to receive empty message");
+        }
+    })
+    .await
+    .expect("requests of the same input are not being handled concurrently");
+}
+
+#[tokio::test]
+pub async fn limit_in_flight_requests_of_same_input() {
+    let sleep_duration: Duration = Duration::from_millis(300u64);
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<()>(1024usize);
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+    let inputs: Vec<InputQueuedImpl> = vec![InputQueuedImpl::new(vec![
+        "slow".to_string(),
+        "slow".to_string(),
+    ])];
+    let dispatch: Dispatch<InputQueuedImpl, LogicRequest> = Dispatch::new(
+        inputs,
+        slow_action(sleep_duration, sender),
+        logic_request_sender,
+        vec![],
+    )
+    .with_max_in_flight_requests(1usize);
+
+    tokio::spawn(dispatch.run(CancellationToken::new()));
+
+    receiver
+        .recv()
+        .await
+        .expect("failed to receive empty message");
+
+    let second_request = timeout(Duration::from_millis(150u64), receiver.recv()).await;
+
+    assert!(second_request.is_err());
+}
+
+#[tokio::test]
+pub async fn reply_with_error_to_unknown_action() {
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+
+    let noop_action: Action<LogicRequest> = Action::new(
+        "create_org".to_string(),
+        Arc::new(move |_request, _sender| Box::pin(async { Ok(Value::Null) })),
+        Vec::new(),
+    );
+    let actions: Arc<HashMap<String, Action<LogicRequest>>> =
+        Arc::new(HashMap::from([("create_org".to_string(), noop_action)]));
+
+    let request = Request::new(
+        RequestHeader::new("create_orgs".to_string(), "".to_string()),
+        Value::Null,
+    );
+
+    let error = execute_action(request, &actions, logic_request_sender)
+        .await
+        .expect_err("expected an error result");
+
+    assert_eq!(ErrorKind::UnknownActionError, error.kind());
+    assert_eq!(
+        Some(&json!({ "action": "create_orgs", "close_actions": ["create_org"] })),
+        error.details()
+    );
+}
+
+#[tokio::test]
+pub async fn reply_with_timeout_error_to_slow_action() {
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+    let (sender, _receiver) = tokio::sync::mpsc::channel::<()>(1);
+
+    let actions: HashMap<String, Action<LogicRequest>> =
+        slow_action(Duration::from_secs(5), sender)
+            .into_iter()
+            .map(|(key, action)| (key, action.with_timeout(Duration::from_millis(100u64))))
+            .collect();
+    let actions = Arc::new(actions);
+
+    let request = Request::new(
+        RequestHeader::new("slow".to_string(), "".to_string()),
+        Value::Null,
+    );
+
+    let error = timeout(
+        Duration::from_secs(1),
+        execute_action(request, &actions, logic_request_sender),
+    )
+    .await
+    .expect("action timeout was not enforced")
+    .expect_err("expected a timeout error");
+
+    assert_eq!(ErrorKind::TimeoutError, error.kind());
+}
+
+#[tokio::test]
+pub async fn reply_with_overloaded_error_beyond_max_concurrency() {
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<()>(2);
+
+    let actions: HashMap<String, Action<LogicRequest>> =
+        slow_action(Duration::from_millis(300u64), sender)
+            .into_iter()
+            .map(|(key, action)| (key, action.with_max_concurrency(1)))
+            .collect();
+    let actions = Arc::new(actions);
+
+    let request = Request::new(
+        RequestHeader::new("slow".to_string(), "".to_string()),
+        Value::Null,
+    );
+
+    let first_execution = {
+        let actions = actions.clone();
+        let request = request.clone();
+        let logic_request_sender = logic_request_sender.clone();
+
+        tokio::spawn(async move { execute_action(request, &actions, logic_request_sender).await })
+    };
+
+    sleep(Duration::from_millis(100u64)).await;
+
+    let error = execute_action(request.clone(), &actions, logic_request_sender.clone())
+        .await
+        .expect_err("expected an overloaded error");
+
+    assert_eq!(ErrorKind::OverloadedError, error.kind());
+    assert_eq!(
+        Ok(Value::Null),
+        first_execution
+            .await
+            .expect("failed to join first execution")
+    );
+    receiver
+        .recv()
+        .await
+        .expect("expected a finished execution");
+
+    assert_eq!(
+        Ok(Value::Null),
+        execute_action(request, &actions, logic_request_sender).await
+    );
+}
+
+#[tokio::test]
+pub async fn stop_blocked_input_when_cancelled() {
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+    let inputs: Vec<InputQueuedImpl> = vec![InputQueuedImpl::new(vec![])];
+    let dispatch: Dispatch<InputQueuedImpl, LogicRequest> =
+        Dispatch::new(inputs, HashMap::new(), logic_request_sender, vec![]);
+
+    let cancellation_token = CancellationToken::new();
+    let handles = dispatch.run(cancellation_token.clone()).await;
+
+    cancellation_token.cancel();
+
+    for handle in handles {
+        let shutdown_report = timeout(Duration::from_millis(200u64), handle)
+            .await
+            .expect("input blocked on receive did not stop")
+            .expect("input dispatch failed");
+
+        assert_eq!(ShutdownReport::new(0usize, 0usize), shutdown_report);
+    }
+}
+
+#[tokio::test]
+pub async fn drain_in_flight_requests_when_cancelled() {
+    let sleep_duration: Duration = Duration::from_millis(200u64);
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<()>(1024usize);
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+    let inputs: Vec<InputQueuedImpl> = vec![InputQueuedImpl::new(vec![
+        "slow".to_string(),
+        "slow".to_string(),
+    ])];
+    let dispatch: Dispatch<InputQueuedImpl, LogicRequest> = Dispatch::new(
+        inputs,
+        slow_action(sleep_duration, sender),
+        logic_request_sender,
+        vec![],
+    )
+    .with_drain_timeout(Duration::from_millis(1000u64));
+
+    let cancellation_token = CancellationToken::new();
+    let mut handles = dispatch.run(cancellation_token.clone()).await;
+
+    sleep(Duration::from_millis(50u64)).await;
+    cancellation_token.cancel();
+
+    let shutdown_report = handles
+        .pop()
+        .expect("expected one handle")
+        .await
+        .expect("input dispatch failed");
+
+    assert_eq!(ShutdownReport::new(2usize, 0usize), shutdown_report);
+    assert!(receiver.try_recv().is_ok());
+    assert!(receiver.try_recv().is_ok());
+}
+
+#[tokio::test]
+pub async fn abandon_in_flight_requests_after_drain_timeout() {
+    let sleep_duration: Duration = Duration::from_millis(1000u64);
+
+    let (sender, _receiver) = tokio::sync::mpsc::channel::<()>(1024usize);
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+    let inputs: Vec<InputQueuedImpl> = vec![InputQueuedImpl::new(vec!["slow".to_string()])];
+    let dispatch: Dispatch<InputQueuedImpl, LogicRequest> = Dispatch::new(
+        inputs,
+        slow_action(sleep_duration, sender),
+        logic_request_sender,
+        vec![],
+    )
+    .with_drain_timeout(Duration::from_millis(100u64));
+
+    let cancellation_token = CancellationToken::new();
+    let mut handles = dispatch.run(cancellation_token.clone()).await;
+
+    sleep(Duration::from_millis(50u64)).await;
+    cancellation_token.cancel();
+
+    let shutdown_report = timeout(
+        Duration::from_millis(500u64),
+        handles.pop().expect("expected one handle"),
+    )
+    .await
+    .expect("drain timeout was not respected")
+    .expect("input dispatch failed");
+
+    assert_eq!(ShutdownReport::new(0usize, 1usize), shutdown_report);
+}
+
+#[cfg(test)]
+pub struct ResponseDecoratorPlugin {
+    id: String,
+}
+
+#[cfg(test)]
+#[async_trait]
+impl InputPlugin for ResponseDecoratorPlugin {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn handle_input_data(
+        &self,
+        input_data: InputData,
+    ) -> Result<InputData, (InputData, Error)> {
+        Ok(input_data)
+    }
+
+    async fn handle_response(
+        &self,
+        header: &RequestHeader,
+        _elapsed: Duration,
+        response: Result<Value, Error>,
+    ) -> Result<Value, Error> {
+        response.map(
+            |value| json!({ "decorated_by": self.id, "action": header.action(), "data": value }),
+        )
+    }
+}
+
+#[tokio::test]
+pub async fn plugins_handle_response_in_reverse_order() {
+    let (reply_sender, mut reply_receiver) = tokio::sync::mpsc::channel::<Value>(1024usize);
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+
+    let noop_action: Action<LogicRequest> = Action::new(
+        "noop".to_string(),
+        Arc::new(move |_request, _sender| Box::pin(async { Ok(json!("value")) })),
+        vec!["filtered".to_string()],
+    );
+    let actions = HashMap::from([("noop".to_string(), noop_action)]);
+
+    let plugins: Vec<Arc<dyn InputPlugin + Send + Sync>> = vec![
+        Arc::new(ResponseDecoratorPlugin {
+            id: "outer".to_string(),
+        }),
+        Arc::new(ResponseDecoratorPlugin {
+            id: "filtered".to_string(),
+        }),
+        Arc::new(ResponseDecoratorPlugin {
+            id: "inner".to_string(),
+        }),
+    ];
+
+    let inputs =
+        vec![InputQueuedImpl::new(vec!["noop".to_string()]).with_reply_sender(reply_sender)];
+    let dispatch: Dispatch<InputQueuedImpl, LogicRequest> =
+        Dispatch::new(inputs, actions, logic_request_sender, plugins);
+
+    tokio::spawn(dispatch.run(CancellationToken::new()));
+
+    let reply: Value = timeout(Duration::from_millis(200u64), reply_receiver.recv())
+        .await
+        .expect("timed out waiting for reply")
+        .expect("failed to receive reply");
+
+    assert_eq!(
+        Ok(json!({
+            "decorated_by": "outer",
+            "action": "noop",
+            "data": {
+                "decorated_by": "inner",
+                "action": "noop",
+                "data": "value"
+            }
+        })),
+        decode_response(reply)
+    );
+}
+
+#[cfg(test)]
+fn versioned_actions() -> HashMap<String, Action<LogicRequest>> {
+    let v1: Action<LogicRequest> = Action::new(
+        "create_org".to_string(),
+        Arc::new(move |_request, _sender| Box::pin(async { Ok(json!("v1")) })),
+        Vec::new(),
+    )
+    .with_version("v1".to_string())
+    .with_deprecation("use v2 instead".to_string());
+    let v2: Action<LogicRequest> = Action::new(
+        "create_org".to_string(),
+        Arc::new(move |_request, _sender| Box::pin(async { Ok(json!("v2")) })),
+        Vec::new(),
+    )
+    .with_version("v2".to_string());
+
+    HashMap::from([
+        ("create_org".to_string(), v2.clone()),
+        (v1.key(), v1),
+        (v2.key(), v2),
+    ])
+}
+
+#[tokio::test]
+pub async fn route_requests_to_action_versions() {
+    let actions = Arc::new(versioned_actions());
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+
+    let headers = vec![
+        (
+            RequestHeader::new("create_org".to_string(), "".to_string()),
+            "v2",
+        ),
+        (
+            RequestHeader::new("create_org@v1".to_string(), "".to_string()),
+            "v1",
+        ),
+        (
+            RequestHeader::new("create_org".to_string(), "".to_string())
+                .with_version("v1".to_string()),
+            "v1",
+        ),
+    ];
+
+    for (header, expected_version) in headers {
+        let result = execute_action(
+            Request::new(header, Value::Null),
+            &actions,
+            logic_request_sender.clone(),
+        )
+        .await;
+
+        assert_eq!(Ok(json!(expected_version)), result);
+    }
+
+    let error = execute_action(
+        Request::new(
+            RequestHeader::new("create_org".to_string(), "".to_string())
+                .with_version("v3".to_string()),
+            Value::Null,
+        ),
+        &actions,
+        logic_request_sender,
+    )
+    .await
+    .expect_err("expected unknown action error");
+
+    assert_eq!(ErrorKind::UnknownActionError, error.kind());
+}
+
+#[test]
+pub fn resolve_default_version_of_requests_without_version() {
+    let actions = Arc::new(versioned_actions());
+
+    let headers = vec![
+        (
+            RequestHeader::new("create_org".to_string(), "".to_string()),
+            "create_org@v2",
+        ),
+        (
+            RequestHeader::new("create_org@v1".to_string(), "".to_string()),
+            "create_org@v1",
+        ),
+        (
+            RequestHeader::new("create_org".to_string(), "".to_string())
+                .with_version("v1".to_string()),
+            "create_org@v1",
+        ),
+        (
+            RequestHeader::new("delete_org".to_string(), "".to_string()),
+            "delete_org",
+        ),
+    ];
+
+    for (mut header, expected_action_key) in headers {
+        resolve_default_version(&mut header, &actions);
+
+        assert_eq!(expected_action_key, requested_action_key(&header));
+    }
+}
+
+#[test]
+pub fn warn_about_deprecated_action_versions() {
+    let actions = Arc::new(versioned_actions());
+
+    let header =
+        RequestHeader::new("create_org@v1".to_string(), "".to_string()).with_id("1".to_string());
+    let warnings = get_warnings_for_action(&requested_action_key(&header), &actions);
+    let reply = build_reply(
+        Ok(json!("v1")),
+        &header,
+        warnings,
+        Duration::from_millis(3u64),
+        ResponseFormat::Envelope,
+    );
+
+    assert_eq!(
+        json!({
+            "version": RESPONSE_VERSION,
+            "status": "ok",
+            "payload": "v1",
+            "request_id": "1",
+            "processing_time_milliseconds": 3,
+            "warnings": ["action 'create_org@v1' is deprecated: use v2 instead"]
+        }),
+        reply
+    );
+    assert!(get_warnings_for_action("create_org", &actions).is_empty());
+}
+
+#[cfg(test)]
+pub struct CachedResponsePlugin {}
+
+#[cfg(test)]
+#[async_trait]
+impl InputPlugin for CachedResponsePlugin {
+    fn id(&self) -> &str {
+        "cached"
+    }
+
+    async fn handle_input_data(
+        &self,
+        mut input_data: InputData,
+    ) -> Result<InputData, (InputData, Error)> {
+        input_data.response = Some(Ok(json!("cached")));
+
+        Ok(input_data)
+    }
+}
+
+#[tokio::test]
+pub async fn reply_with_plugin_response_without_executing_action() {
+    let (reply_sender, mut reply_receiver) = tokio::sync::mpsc::channel::<Value>(1024usize);
+    let (logic_request_sender, _) = async_channel::unbounded::<LogicRequest>();
+
+    let executed_action: Action<LogicRequest> = Action::new(
+        "noop".to_string(),
+        Arc::new(move |_request, _sender| Box::pin(async { Ok(json!("executed")) })),
+        Vec::new(),
+    );
+    let actions = HashMap::from([("noop".to_string(), executed_action)]);
+    let plugins: Vec<Arc<dyn InputPlugin + Send + Sync>> = vec![Arc::new(CachedResponsePlugin {})];
+
+    let inputs =
+        vec![InputQueuedImpl::new(vec!["noop".to_string()]).with_reply_sender(reply_sender)];
+    let dispatch: Dispatch<InputQueuedImpl, LogicRequest> =
+        Dispatch::new(inputs, actions, logic_request_sender, plugins);
+
+    tokio::spawn(dispatch.run(CancellationToken::new()));
+
+    let reply: Value = timeout(Duration::from_millis(200u64), reply_receiver.recv())
+        .await
+        .expect("timed out waiting for reply")
+        .expect("failed to receive reply");
+
+    assert_eq!(Ok(json!("cached")), decode_response(reply));
+}
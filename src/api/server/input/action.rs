@@ -1,14 +1,29 @@
-use serde::{de::DeserializeOwned, Deserialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use tokio::sync::Semaphore;
 
 use crate::{
     api::{server::input::executor::Executor, shared::request::Request},
     core::error::{Error, ErrorKind},
 };
 
+///
+/// Separates the id of an action from its version within the keys of the actions map and the
+/// action requested through the request header, e.g. `create_org@v2`.
+///
+pub const ACTION_VERSION_SEPARATOR: char = '@';
+
 pub struct Action<LogicRequestType> {
     id: String,
     executor: Executor<LogicRequestType>,
     filter_out_plugins: Vec<String>,
+    version: Option<String>,
+    deprecation: Option<String>,
+    timeout: Option<Duration>,
+    max_concurrency: Option<usize>,
+    concurrency_limiter: Option<Arc<Semaphore>>,
 }
 
 impl<LogicRequestType> Action<LogicRequestType> {
@@ -21,13 +36,95 @@ impl<LogicRequestType> Action<LogicRequestType> {
             id,
             executor,
             filter_out_plugins,
+            version: None,
+            deprecation: None,
+            timeout: None,
+            max_concurrency: None,
+            concurrency_limiter: None,
         }
     }
 
+    pub fn with_version(mut self, version: String) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    ///
+    /// Ids of the plugins which do not handle the requests of the action, e.g. the
+    /// authentication plugin for a login action.
+    ///
+    pub fn with_filter_out_plugins(mut self, filter_out_plugins: Vec<String>) -> Self {
+        self.filter_out_plugins = filter_out_plugins;
+        self
+    }
+
+    ///
+    /// Marks the action as deprecated, the given message is sent as a warning along the replies
+    /// of the action.
+    ///
+    pub fn with_deprecation(mut self, message: String) -> Self {
+        self.deprecation = Some(message);
+        self
+    }
+
+    ///
+    /// Maximum duration of each execution of the action, after which the caller receives a
+    /// `TimeoutError`.
+    ///
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    ///
+    /// Maximum amount of concurrent executions of the action, the requests received beyond it
+    /// are rejected with an `OverloadedError`. The limit is shared among the clones of the action.
+    /// A maximum of 0 is raised to 1, as an action which cannot run at all is not meant to be
+    /// registered.
+    ///
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        let max_concurrency = max_concurrency.max(1);
+
+        self.max_concurrency = Some(max_concurrency);
+        self.concurrency_limiter = Some(Arc::new(Semaphore::new(max_concurrency)));
+        self
+    }
+
     pub fn id(&self) -> &str {
         self.id.as_str()
     }
 
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    pub fn deprecation(&self) -> Option<&str> {
+        self.deprecation.as_deref()
+    }
+
+    ///
+    /// Key under which the action is expected within the actions map: the id for unversioned
+    /// actions and `{id}@{version}` for versioned ones.
+    ///
+    pub fn key(&self) -> String {
+        match &self.version {
+            Some(version) => versioned_action_key(&self.id, version),
+            None => self.id.clone(),
+        }
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn max_concurrency(&self) -> Option<usize> {
+        self.max_concurrency
+    }
+
+    pub fn concurrency_limiter(&self) -> Option<Arc<Semaphore>> {
+        self.concurrency_limiter.clone()
+    }
+
     pub fn executor(&self) -> Executor<LogicRequestType> {
         self.executor.clone()
     }
@@ -37,6 +134,25 @@ impl<LogicRequestType> Action<LogicRequestType> {
     }
 }
 
+impl<LogicRequestType> Clone for Action<LogicRequestType> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            executor: self.executor.clone(),
+            filter_out_plugins: self.filter_out_plugins.clone(),
+            version: self.version.clone(),
+            deprecation: self.deprecation.clone(),
+            timeout: self.timeout,
+            max_concurrency: self.max_concurrency,
+            concurrency_limiter: self.concurrency_limiter.clone(),
+        }
+    }
+}
+
+pub fn versioned_action_key(id: &str, version: &str) -> String {
+    format!("{}{}{}", id, ACTION_VERSION_SEPARATOR, version)
+}
+
 pub fn extract_payload<PayloadType: DeserializeOwned>(
     request: &Request,
 ) -> Result<PayloadType, Error> {
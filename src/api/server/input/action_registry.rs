@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use async_channel::Sender;
+use serde::de::DeserializeOwned;
+#[cfg(test)]
+use serde::Deserialize;
+use serde::Serialize;
+#[cfg(test)]
+use serde_json::json;
+
+use crate::api::server::input::action::{extract_payload, versioned_action_key, Action};
+use crate::api::server::input::executor::Executor;
+#[cfg(test)]
+use crate::api::shared::request::Request;
+#[cfg(test)]
+use crate::api::shared::request_header::RequestHeader;
+use crate::core::error::{Error, ErrorKind};
+
+///
+/// Builder of the actions exposed through the API, which turns typed handlers into executors.
+///
+pub struct ActionRegistry<LogicRequestType> {
+    actions: HashMap<String, Action<LogicRequestType>>,
+    default_versions: HashMap<String, String>,
+}
+
+impl<LogicRequestType: 'static + Send> ActionRegistry<LogicRequestType> {
+    pub fn new() -> ActionRegistry<LogicRequestType> {
+        ActionRegistry {
+            actions: HashMap::new(),
+            default_versions: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Registers a handler which receives the request's payload already deserialized, and whose
+    /// result is serialized into the reply.
+    ///
+    pub fn register<PayloadType, OkResultType, HandlerType, FutureType>(
+        &mut self,
+        id: &str,
+        handler: HandlerType,
+    ) -> Result<&mut ActionRegistry<LogicRequestType>, Error>
+    where
+        PayloadType: 'static + DeserializeOwned,
+        OkResultType: 'static + Serialize,
+        HandlerType:
+            'static + Fn(PayloadType, Sender<LogicRequestType>) -> FutureType + Send + Sync,
+        FutureType: 'static + Future<Output = Result<OkResultType, Error>> + Send + Sync,
+    {
+        self.register_action(Action::new(
+            id.to_string(),
+            typed_executor(handler),
+            Vec::new(),
+        ))
+    }
+
+    ///
+    /// Registers a version of an action, reachable as `{id}@{version}` or through the version of
+    /// the request header.
+    ///
+    pub fn register_version<PayloadType, OkResultType, HandlerType, FutureType>(
+        &mut self,
+        id: &str,
+        version: &str,
+        handler: HandlerType,
+    ) -> Result<&mut ActionRegistry<LogicRequestType>, Error>
+    where
+        PayloadType: 'static + DeserializeOwned,
+        OkResultType: 'static + Serialize,
+        HandlerType:
+            'static + Fn(PayloadType, Sender<LogicRequestType>) -> FutureType + Send + Sync,
+        FutureType: 'static + Future<Output = Result<OkResultType, Error>> + Send + Sync,
+    {
+        self.register_action(
+            Action::new(id.to_string(), typed_executor(handler), Vec::new())
+                .with_version(version.to_string()),
+        )
+    }
+
+    pub fn register_action(
+        &mut self,
+        action: Action<LogicRequestType>,
+    ) -> Result<&mut ActionRegistry<LogicRequestType>, Error> {
+        let key = action.key();
+
+        if self.actions.contains_key(&key)
+            || (action.version().is_none() && self.default_versions.contains_key(&key))
+        {
+            return Err(Error::new(
+                ErrorKind::InitializationError,
+                format!("action '{}' is already registered", key),
+            ));
+        }
+
+        self.actions.insert(key, action);
+
+        Ok(self)
+    }
+
+    ///
+    /// Routes the requests which do not ask for a version of the action to the given version.
+    ///
+    pub fn set_default_version(
+        &mut self,
+        id: &str,
+        version: &str,
+    ) -> Result<&mut ActionRegistry<LogicRequestType>, Error> {
+        let key = versioned_action_key(id, version);
+
+        if !self.actions.contains_key(&key) {
+            return Err(Error::new(
+                ErrorKind::InitializationError,
+                format!("action '{}' is not registered", key),
+            ));
+        }
+
+        if self.actions.contains_key(id) || self.default_versions.contains_key(id) {
+            return Err(Error::new(
+                ErrorKind::InitializationError,
+                format!("action '{}' already has a default version", id),
+            ));
+        }
+
+        self.default_versions
+            .insert(id.to_string(), version.to_string());
+
+        Ok(self)
+    }
+
+    ///
+    /// Marks a version of an action as deprecated, replies of that version carry the message as
+    /// a warning.
+    ///
+    pub fn deprecate(
+        &mut self,
+        id: &str,
+        version: &str,
+        message: &str,
+    ) -> Result<&mut ActionRegistry<LogicRequestType>, Error> {
+        let key = versioned_action_key(id, version);
+
+        let action = match self.actions.remove(&key) {
+            Some(action) => action,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!("action '{}' is not registered", key),
+                ))
+            }
+        };
+
+        self.actions
+            .insert(key, action.with_deprecation(message.to_string()));
+
+        Ok(self)
+    }
+
+    ///
+    /// Sets the plugins which do not handle the requests of a registered action, given as its
+    /// id or as `{id}@{version}` for a version, e.g. to let a login action skip the
+    /// authentication plugin.
+    ///
+    pub fn filter_out_plugins(
+        &mut self,
+        action: &str,
+        plugin_ids: &[&str],
+    ) -> Result<&mut ActionRegistry<LogicRequestType>, Error> {
+        let registered_action = match self.actions.remove(action) {
+            Some(registered_action) => registered_action,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!("action '{}' is not registered", action),
+                ))
+            }
+        };
+
+        let filter_out_plugins = plugin_ids
+            .iter()
+            .map(|plugin_id| plugin_id.to_string())
+            .collect();
+
+        self.actions.insert(
+            action.to_string(),
+            registered_action.with_filter_out_plugins(filter_out_plugins),
+        );
+
+        Ok(self)
+    }
+
+    pub fn build(mut self) -> HashMap<String, Action<LogicRequestType>> {
+        for (id, version) in self.default_versions {
+            if let Some(action) = self.actions.get(&versioned_action_key(&id, &version)) {
+                let default_action = action.clone();
+                self.actions.insert(id, default_action);
+            }
+        }
+
+        self.actions
+    }
+}
+
+impl<LogicRequestType: 'static + Send> Default for ActionRegistry<LogicRequestType> {
+    fn default() -> Self {
+        ActionRegistry::new()
+    }
+}
+
+///
+/// Wraps a typed handler into an executor, extracting the payload from the request and
+/// serializing the handler's result.
+///
+pub fn typed_executor<LogicRequestType, PayloadType, OkResultType, HandlerType, FutureType>(
+    handler: HandlerType,
+) -> Executor<LogicRequestType>
+where
+    LogicRequestType: 'static + Send,
+    PayloadType: 'static + DeserializeOwned,
+    OkResultType: 'static + Serialize,
+    HandlerType: 'static + Fn(PayloadType, Sender<LogicRequestType>) -> FutureType + Send + Sync,
+    FutureType: 'static + Future<Output = Result<OkResultType, Error>> + Send + Sync,
+{
+    Arc::new(move |request, sender| {
+        // the handler's future is created outside the executor's future so the payload does
+        // not need to be sendable between threads
+        let handler_future =
+            extract_payload::<PayloadType>(&request).map(|payload| handler(payload, sender));
+
+        Box::pin(async move {
+            let ok_result = handler_future?.await?;
+
+            match serde_json::to_value(ok_result) {
+                Ok(serialized_ok_result) => Ok(serialized_ok_result),
+                Err(error) => Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to serialize result: {}", &error),
+                )),
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+#[derive(Deserialize)]
+pub struct GreetPayload {
+    name: String,
+}
+
+#[cfg(test)]
+async fn greet(payload: GreetPayload, _sender: Sender<()>) -> Result<String, Error> {
+    Ok(format!("hello {}", payload.name))
+}
+
+#[tokio::test]
+pub async fn registered_handler_receives_typed_payload() {
+    let mut registry: ActionRegistry<()> = ActionRegistry::new();
+    registry
+        .register("greet", greet)
+        .expect("failed to register action");
+
+    let actions = registry.build();
+    let executor = actions.get("greet").expect("expected action").executor();
+
+    let (sender, _) = async_channel::unbounded::<()>();
+    let request = Request::new(
+        RequestHeader::new("greet".to_string(), "".to_string()),
+        json!({ "name": "world" }),
+    );
+
+    assert_eq!(Ok(json!("hello world")), executor(request, sender).await);
+}
+
+#[tokio::test]
+pub async fn registered_handler_rejects_invalid_payload() {
+    let mut registry: ActionRegistry<()> = ActionRegistry::new();
+    registry
+        .register("greet", greet)
+        .expect("failed to register action");
+
+    let actions = registry.build();
+    let executor = actions.get("greet").expect("expected action").executor();
+
+    let (sender, _) = async_channel::unbounded::<()>();
+    let request = Request::new(
+        RequestHeader::new("greet".to_string(), "".to_string()),
+        json!({ "surname": "world" }),
+    );
+
+    let error = executor(request, sender)
+        .await
+        .expect_err("expected invalid payload error");
+
+    assert_eq!(ErrorKind::RequestError, error.kind());
+}
+
+#[test]
+pub fn reject_duplicated_action_ids() {
+    let mut registry: ActionRegistry<()> = ActionRegistry::new();
+    registry
+        .register("greet", greet)
+        .expect("failed to register action");
+
+    let error = registry
+        .register("greet", greet)
+        .err()
+        .expect("expected duplicated action error");
+
+    assert_eq!(ErrorKind::InitializationError, error.kind());
+}
+
+#[cfg(test)]
+async fn greet_v2(payload: GreetPayload, _sender: Sender<()>) -> Result<String, Error> {
+    Ok(format!("hi {}", payload.name))
+}
+
+#[test]
+pub fn default_version_is_registered_under_action_id() {
+    let mut registry: ActionRegistry<()> = ActionRegistry::new();
+    registry
+        .register_version("greet", "v1", greet)
+        .and_then(|registry| registry.register_version("greet", "v2", greet_v2))
+        .and_then(|registry| registry.set_default_version("greet", "v2"))
+        .and_then(|registry| registry.deprecate("greet", "v1", "use v2 instead"))
+        .expect("failed to register actions");
+
+    let actions = registry.build();
+
+    assert_eq!(Some("v2"), actions.get("greet").unwrap().version());
+    assert_eq!(
+        Some("use v2 instead"),
+        actions.get("greet@v1").unwrap().deprecation()
+    );
+    assert!(actions.contains_key("greet@v2"));
+}
+
+#[test]
+pub fn reject_default_version_of_unversioned_action() {
+    let mut registry: ActionRegistry<()> = ActionRegistry::new();
+    registry
+        .register("greet", greet)
+        .and_then(|registry| registry.register_version("greet", "v2", greet_v2))
+        .expect("failed to register actions");
+
+    let error = registry
+        .set_default_version("greet", "v2")
+        .err()
+        .expect("expected default version error");
+
+    assert_eq!(ErrorKind::InitializationError, error.kind());
+}
+
+#[test]
+pub fn filter_out_plugins_of_registered_actions() {
+    let mut registry: ActionRegistry<()> = ActionRegistry::new();
+    registry
+        .register("login", greet)
+        .expect("failed to register action")
+        .register_version("greet", "v1", greet)
+        .expect("failed to register action")
+        .set_default_version("greet", "v1")
+        .expect("failed to set default version")
+        .filter_out_plugins("login", &["jwt"])
+        .expect("failed to filter out plugins")
+        .filter_out_plugins("greet@v1", &["jwt", "rate_limit"])
+        .expect("failed to filter out plugins");
+
+    assert!(registry.filter_out_plugins("unknown", &["jwt"]).is_err());
+
+    let actions = registry.build();
+
+    assert_eq!(
+        vec!["jwt".to_string()],
+        actions
+            .get("login")
+            .expect("expected action")
+            .filter_out_plugins()
+    );
+    assert_eq!(
+        vec!["jwt".to_string(), "rate_limit".to_string()],
+        actions
+            .get("greet")
+            .expect("expected action")
+            .filter_out_plugins()
+    );
+}
@@ -1,16 +1,23 @@
-use std::{fmt::Display, time::Duration};
+use std::fmt::Display;
 
 use async_channel::Sender;
 use serde::Serialize;
 use serde_json::Value;
-use tokio::{sync::oneshot::Receiver, time::timeout};
+use tokio::sync::oneshot::Receiver;
 
 use crate::core::error::{Error, ErrorKind};
 
+///
+/// Sends the logic request and waits for its result, which is bounded by the timeout of the
+/// action (`Action::with_timeout`).
+///
+#[tracing::instrument(
+    name = "api_action",
+    skip(logic_request, logic_request_sender, receiver)
+)]
 pub async fn api_action<OkResultType: Serialize, ErrResultType: Display, LogicRequestType>(
     logic_request: LogicRequestType,
     logic_request_sender: Sender<LogicRequestType>,
-    timeout_after_milliseconds: u64,
     receiver: Receiver<Result<OkResultType, ErrResultType>>,
 ) -> Result<Value, Error> {
     match logic_request_sender.send(logic_request).await {
@@ -23,32 +30,23 @@ pub async fn api_action<OkResultType: Serialize, ErrResultType: Display, LogicRe
         }
     }
 
-    let ok_result: OkResultType =
-        match timeout(Duration::from_millis(timeout_after_milliseconds), receiver).await {
-            Ok(result) => match result {
-                Ok(result) => match result {
-                    Ok(ok_result) => ok_result,
-                    Err(error) => {
-                        return Err(Error::new(
-                            ErrorKind::RequestError,
-                            format!("failed to handle request: {}", &error),
-                        ))
-                    }
-                },
-                Err(error) => {
-                    return Err(Error::new(
-                        ErrorKind::RequestError,
-                        format!("failed to receive logic request: {}", &error),
-                    ))
-                }
-            },
-            Err(_) => {
+    let ok_result: OkResultType = match receiver.await {
+        Ok(result) => match result {
+            Ok(ok_result) => ok_result,
+            Err(error) => {
                 return Err(Error::new(
-                    ErrorKind::ApiError,
-                    "timed out waiting for logic result",
+                    ErrorKind::RequestError,
+                    format!("failed to handle request: {}", &error),
                 ))
             }
-        };
+        },
+        Err(error) => {
+            return Err(Error::new(
+                ErrorKind::RequestError,
+                format!("failed to receive logic request: {}", &error),
+            ))
+        }
+    };
 
     let serialized_ok_result = match serde_json::to_value(ok_result) {
         Ok(serialized_ok_result) => serialized_ok_result,
@@ -0,0 +1,12 @@
+use async_trait::async_trait;
+
+use crate::core::error::Error;
+
+///
+/// Authenticates the token of inputs which keep a connection open, e.g. a WebSocket, once per
+/// connection instead of once per request.
+///
+#[async_trait]
+pub trait ConnectionAuthenticator {
+    async fn authenticate(&self, token: &str) -> Result<(), Error>;
+}
@@ -6,5 +6,17 @@ use crate::core::error::Error;
 ///
 #[async_trait::async_trait]
 pub trait Input {
+    ///
+    /// Must be cancel safe: the dispatch drops the pending call when it stops, so a request
+    /// taken from its source must not be lost when the call is dropped before returning it.
+    ///
     async fn receive(&mut self) -> Result<InputData, Error>;
+
+    ///
+    /// Called once the dispatch has stopped receiving and drained the in-flight requests, so
+    /// the input can release its resources and hand back what it has not processed.
+    ///
+    async fn stop(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
 }
@@ -1,13 +1,25 @@
+use serde_json::Value;
+
 use crate::api::server::input::replier::Replier;
 use crate::api::shared::request::Request;
+use crate::core::error::Error;
 
 pub struct InputData {
     pub request: Request,
     pub replier: Replier,
+    ///
+    /// Response set by a plugin, e.g. a cached one, in which case the action is not executed
+    /// and the response is replied instead.
+    ///
+    pub response: Option<Result<Value, Error>>,
 }
 
 impl InputData {
     pub fn new(request: Request, replier: Replier) -> InputData {
-        InputData { request, replier }
+        InputData {
+            request,
+            replier,
+            response: None,
+        }
     }
 }
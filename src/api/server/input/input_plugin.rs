@@ -1,5 +1,9 @@
+use std::time::Duration;
+
 use crate::api::server::input::input_data::InputData;
+use crate::api::shared::request_header::RequestHeader;
 use async_trait::async_trait;
+use serde_json::Value;
 
 use crate::core::error::Error;
 
@@ -10,4 +14,20 @@ pub trait InputPlugin {
         &self,
         input_data: InputData,
     ) -> Result<InputData, (InputData, Error)>;
+
+    ///
+    /// Called with the result of the action before it is replied, along with the header of the
+    /// request and the time elapsed since it was received. The returned result is the one
+    /// passed to the next plugin and, eventually, replied. Plugins see responses in the reverse
+    /// order in which they saw the requests. When a later plugin rejects the request, its error
+    /// is the response seen by the plugins which already handled the request.
+    ///
+    async fn handle_response(
+        &self,
+        _header: &RequestHeader,
+        _elapsed: Duration,
+        response: Result<Value, Error>,
+    ) -> Result<Value, Error> {
+        response
+    }
 }
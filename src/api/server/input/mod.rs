@@ -1,6 +1,9 @@
 pub mod action;
+pub mod action_registry;
 pub mod api_action;
+pub mod connection_authenticator;
 pub mod executor;
+#[allow(clippy::module_inception)]
 pub mod input;
 pub mod input_data;
 pub mod input_plugin;
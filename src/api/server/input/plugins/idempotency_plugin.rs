@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::warn;
+#[cfg(test)]
+use serde_json::json;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+use tokio::time::timeout;
+
+use crate::api::server::dispatch::{requested_action_key, ACTION_EXECUTED_EXTRA};
+use crate::api::server::input::input_data::InputData;
+use crate::api::server::input::input_plugin::InputPlugin;
+use crate::api::server::input::plugins::idempotency_store::{
+    IdempotencyStore, InMemoryIdempotencyStore,
+};
+use crate::api::server::input::plugins::jwt_plugin::JWT_CLAIMS_EXTRA_KEY;
+#[cfg(test)]
+use crate::api::shared::request::Request;
+use crate::api::shared::request_header::RequestHeader;
+use crate::core::error::{Error, ErrorKind};
+
+pub const IDEMPOTENCY_PLUGIN_ID: &str = "idempotency";
+
+///
+/// Header extra which carries the idempotency key of the request.
+///
+pub const IDEMPOTENCY_KEY_EXTRA: &str = "idempotency_key";
+
+///
+/// Header extra which identifies the request executing the action for an idempotency key.
+///
+const IDEMPOTENCY_OWNER_EXTRA: &str = "idempotency_owner";
+
+pub const DEFAULT_IDEMPOTENCY_WAIT_TIMEOUT: Duration = Duration::from_secs(30u64);
+
+const KEY_SEPARATOR: char = '\u{1f}';
+
+///
+/// Part of the request which identifies the caller, so callers never share their responses.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum IdempotencyScope {
+    #[default]
+    Token,
+    ///
+    /// Subject of the claims verified by the JWT plugin, so the tokens of the same subject share
+    /// their responses.
+    ///
+    JwtSubject,
+    ///
+    /// Value of the given header extra, e.g. the tenant of the request.
+    ///
+    Extra(String),
+}
+
+///
+/// Owner of each key being executed along the sender which is dropped once it finishes.
+///
+type InFlightExecutions = HashMap<String, (String, watch::Sender<()>)>;
+
+enum Execution {
+    Stored(Result<Value, Error>),
+    Owned(String),
+}
+
+///
+/// Plugin which replies to the requests carrying an already seen idempotency key with the
+/// response of the first one, instead of executing the action again. Duplicates received while
+/// the first request is still in flight wait for its response. Keys are scoped by action and
+/// caller.
+///
+/// Only the responses of executed actions are stored, so rejections by other plugins are not
+/// replayed, neither are responses with transient errors, e.g. timeouts, so the request can be
+/// retried.
+///
+pub struct IdempotencyPlugin {
+    store: Arc<dyn IdempotencyStore + Send + Sync>,
+    wait_timeout: Duration,
+    scope: IdempotencyScope,
+    in_flight: Arc<Mutex<InFlightExecutions>>,
+}
+
+///
+/// Releases the key once the request owning its execution is dropped without its response
+/// being handled, e.g. because it was aborted or panicked, so duplicates do not wait for it.
+///
+struct InFlightGuard {
+    in_flight: Arc<Mutex<InFlightExecutions>>,
+    key: String,
+    owner: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut in_flight = lock_in_flight(&self.in_flight);
+
+        let is_owner = match in_flight.get(&self.key) {
+            Some((owner, _)) => *owner == self.owner,
+            None => false,
+        };
+
+        if is_owner {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+impl IdempotencyPlugin {
+    pub fn new(store: Arc<dyn IdempotencyStore + Send + Sync>) -> IdempotencyPlugin {
+        IdempotencyPlugin {
+            store,
+            wait_timeout: DEFAULT_IDEMPOTENCY_WAIT_TIMEOUT,
+            scope: IdempotencyScope::default(),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    ///
+    /// Maximum time a duplicate waits for the first request's response before being rejected.
+    ///
+    pub fn with_wait_timeout(mut self, wait_timeout: Duration) -> IdempotencyPlugin {
+        self.wait_timeout = wait_timeout;
+        self
+    }
+
+    ///
+    /// Part of the request which identifies the caller, the token by default.
+    ///
+    pub fn with_scope(mut self, scope: IdempotencyScope) -> IdempotencyPlugin {
+        self.scope = scope;
+        self
+    }
+
+    fn idempotency_key(&self, header: &RequestHeader) -> Option<String> {
+        let key = header.get_extra(IDEMPOTENCY_KEY_EXTRA)?;
+
+        Some(format!(
+            "{}{}{}{}{}",
+            requested_action_key(header),
+            KEY_SEPARATOR,
+            self.caller(header),
+            KEY_SEPARATOR,
+            key
+        ))
+    }
+
+    ///
+    /// Hash of the caller's scope, so stores never keep the callers' tokens.
+    ///
+    fn caller(&self, header: &RequestHeader) -> String {
+        let scope = match &self.scope {
+            IdempotencyScope::Token => header.token().to_string(),
+            IdempotencyScope::JwtSubject => header
+                .get_extra(JWT_CLAIMS_EXTRA_KEY)
+                .and_then(|claims| serde_json::from_str::<Value>(claims).ok())
+                .and_then(|claims| {
+                    claims
+                        .get("sub")
+                        .and_then(|subject| subject.as_str())
+                        .map(|subject| subject.to_string())
+                })
+                .unwrap_or_default(),
+            IdempotencyScope::Extra(extra) => header.get_extra(extra).cloned().unwrap_or_default(),
+        };
+
+        format!("{:x}", Sha256::digest(scope.as_bytes()))
+    }
+
+    fn in_flight(&self) -> MutexGuard<'_, InFlightExecutions> {
+        lock_in_flight(&self.in_flight)
+    }
+
+    ///
+    /// Stored response for the key, otherwise the caller becomes the owner of the key's execution.
+    ///
+    async fn stored_response_or_own(&self, key: &str) -> Result<Execution, Error> {
+        loop {
+            if let Some(response) = self.store.get(key).await? {
+                return Ok(Execution::Stored(response));
+            }
+
+            let owner = uuid::Uuid::new_v4().to_string();
+            let receiver = {
+                let mut in_flight = self.in_flight();
+
+                match in_flight.get(key) {
+                    Some((_, sender)) => Some(sender.subscribe()),
+                    None => {
+                        in_flight.insert(key.to_string(), (owner.clone(), watch::channel(()).0));
+                        None
+                    }
+                }
+            };
+
+            let mut receiver = match receiver {
+                Some(receiver) => receiver,
+                None => {
+                    // the first execution may have finished between the lookup and taking over
+                    if let Some(response) = self.store.get(key).await? {
+                        self.in_flight().remove(key);
+                        return Ok(Execution::Stored(response));
+                    }
+
+                    return Ok(Execution::Owned(owner));
+                }
+            };
+
+            // the sender is dropped once the first execution finishes
+            if timeout(self.wait_timeout, receiver.changed())
+                .await
+                .is_err()
+            {
+                return Err(Error::new(
+                    ErrorKind::OverloadedError,
+                    "a request with the same idempotency key is still being processed",
+                ));
+            }
+        }
+    }
+}
+
+impl Default for IdempotencyPlugin {
+    fn default() -> Self {
+        IdempotencyPlugin::new(Arc::new(InMemoryIdempotencyStore::default()))
+    }
+}
+
+fn lock_in_flight(in_flight: &Mutex<InFlightExecutions>) -> MutexGuard<'_, InFlightExecutions> {
+    match in_flight.lock() {
+        Ok(in_flight) => in_flight,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn is_transient(response: &Result<Value, Error>) -> bool {
+    match response {
+        Ok(_) => false,
+        Err(error) => error.kind().is_transient(),
+    }
+}
+
+#[async_trait]
+impl InputPlugin for IdempotencyPlugin {
+    fn id(&self) -> &str {
+        IDEMPOTENCY_PLUGIN_ID
+    }
+
+    async fn handle_input_data(
+        &self,
+        mut input_data: InputData,
+    ) -> Result<InputData, (InputData, Error)> {
+        let key = match self.idempotency_key(input_data.request.header()) {
+            Some(key) => key,
+            None => return Ok(input_data),
+        };
+
+        match self.stored_response_or_own(&key).await {
+            Ok(Execution::Stored(response)) => {
+                input_data.response = Some(response);
+                Ok(input_data)
+            }
+            Ok(Execution::Owned(owner)) => {
+                input_data
+                    .request
+                    .mut_header()
+                    .add_extra(IDEMPOTENCY_OWNER_EXTRA.to_string(), owner.clone());
+
+                // the replier lives as long as the request, so it carries the guard
+                let guard = InFlightGuard {
+                    in_flight: self.in_flight.clone(),
+                    key,
+                    owner,
+                };
+                let replier = input_data.replier;
+                input_data.replier = Arc::new(move |value| {
+                    let _guard = &guard;
+                    replier(value)
+                });
+
+                Ok(input_data)
+            }
+            Err(error) => Err((input_data, error)),
+        }
+    }
+
+    async fn handle_response(
+        &self,
+        header: &RequestHeader,
+        _elapsed: Duration,
+        response: Result<Value, Error>,
+    ) -> Result<Value, Error> {
+        let key = match self.idempotency_key(header) {
+            Some(key) => key,
+            None => return response,
+        };
+
+        // only the request which owns the key's execution stores its response
+        let is_owner = match (
+            self.in_flight().get(&key),
+            header.get_extra(IDEMPOTENCY_OWNER_EXTRA),
+        ) {
+            (Some((owner, _)), Some(header_owner)) => owner == header_owner,
+            _ => false,
+        };
+
+        if !is_owner {
+            return response;
+        }
+
+        // rejections by other plugins are not stored, e.g. before the caller is authorized
+        if header.has_extra(ACTION_EXECUTED_EXTRA) && !is_transient(&response) {
+            if let Err(error) = self.store.set(&key, response.clone()).await {
+                warn!("failed to store idempotent response: {}", error);
+            }
+        }
+
+        self.in_flight().remove(&key);
+
+        response
+    }
+}
+
+#[cfg(test)]
+fn create_input_data(key: &str) -> InputData {
+    create_caller_input_data("", key)
+}
+
+#[cfg(test)]
+fn create_caller_input_data(token: &str, key: &str) -> InputData {
+    let mut header = RequestHeader::new("create_org".to_string(), token.to_string());
+    header.add_extra(IDEMPOTENCY_KEY_EXTRA.to_string(), key.to_string());
+
+    InputData::new(
+        Request::new(header, Value::Null),
+        Arc::new(|_| Box::pin(async { Ok(()) })),
+    )
+}
+
+///
+/// Header as handed to the response hooks once the dispatch executed the action.
+///
+#[cfg(test)]
+fn executed(header: &RequestHeader) -> RequestHeader {
+    let mut header = header.clone();
+    header.add_extra(ACTION_EXECUTED_EXTRA.to_string(), "true".to_string());
+
+    header
+}
+
+#[tokio::test]
+pub async fn replay_stored_response_for_duplicates() {
+    let plugin = IdempotencyPlugin::default();
+
+    let input_data = match plugin.handle_input_data(create_input_data("1")).await {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected first request to be accepted: {}", error),
+    };
+    assert!(input_data.response.is_none());
+
+    plugin
+        .handle_response(
+            &executed(input_data.request.header()),
+            Duration::ZERO,
+            Ok(json!("created")),
+        )
+        .await
+        .expect("expected response");
+
+    let duplicate = match plugin.handle_input_data(create_input_data("1")).await {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected duplicate to be accepted: {}", error),
+    };
+
+    assert_eq!(Some(Ok(json!("created"))), duplicate.response);
+}
+
+#[tokio::test]
+pub async fn concurrent_duplicates_wait_for_first_execution() {
+    let plugin = Arc::new(IdempotencyPlugin::default());
+
+    let input_data = match plugin.handle_input_data(create_input_data("1")).await {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected first request to be accepted: {}", error),
+    };
+
+    let duplicate = {
+        let plugin = plugin.clone();
+        tokio::spawn(async move { plugin.handle_input_data(create_input_data("1")).await })
+    };
+
+    tokio::time::sleep(Duration::from_millis(100u64)).await;
+    assert!(!duplicate.is_finished());
+
+    plugin
+        .handle_response(
+            &executed(input_data.request.header()),
+            Duration::ZERO,
+            Ok(json!("created")),
+        )
+        .await
+        .expect("expected response");
+
+    let duplicate = match duplicate.await.expect("failed to join duplicate") {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected duplicate to be accepted: {}", error),
+    };
+
+    assert_eq!(Some(Ok(json!("created"))), duplicate.response);
+}
+
+#[tokio::test]
+pub async fn release_key_of_aborted_request() {
+    let plugin = Arc::new(IdempotencyPlugin::default().with_wait_timeout(Duration::from_secs(1)));
+
+    let request = {
+        let plugin = plugin.clone();
+        tokio::spawn(async move {
+            let input_data = plugin.handle_input_data(create_input_data("1")).await;
+            tokio::time::sleep(Duration::from_secs(60u64)).await;
+            drop(input_data);
+        })
+    };
+
+    tokio::time::sleep(Duration::from_millis(100u64)).await;
+    request.abort();
+    assert!(request.await.is_err());
+
+    let retry = match plugin.handle_input_data(create_input_data("1")).await {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected retry to own the key: {}", error),
+    };
+
+    assert!(retry.response.is_none());
+    assert!(retry
+        .request
+        .header()
+        .get_extra(IDEMPOTENCY_OWNER_EXTRA)
+        .is_some());
+}
+
+#[tokio::test]
+pub async fn keep_responses_of_each_caller_apart() {
+    let plugin = IdempotencyPlugin::default();
+
+    let input_data = match plugin
+        .handle_input_data(create_caller_input_data("first", "1"))
+        .await
+    {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected first request to be accepted: {}", error),
+    };
+
+    plugin
+        .handle_response(
+            &executed(input_data.request.header()),
+            Duration::ZERO,
+            Ok(json!("created")),
+        )
+        .await
+        .expect("expected response");
+
+    let other_caller = match plugin
+        .handle_input_data(create_caller_input_data("second", "1"))
+        .await
+    {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected other caller to be accepted: {}", error),
+    };
+
+    assert!(other_caller.response.is_none());
+}
+
+#[tokio::test]
+pub async fn skip_storing_responses_of_actions_not_executed() {
+    let plugin = IdempotencyPlugin::default();
+
+    let input_data = match plugin.handle_input_data(create_input_data("1")).await {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected first request to be accepted: {}", error),
+    };
+
+    // e.g. a later plugin rejected the request before the action was executed
+    let response = plugin
+        .handle_response(
+            input_data.request.header(),
+            Duration::ZERO,
+            Err(Error::new(ErrorKind::UnauthorizedError, "invalid token")),
+        )
+        .await;
+    assert!(response.is_err());
+
+    let retry = match plugin.handle_input_data(create_input_data("1")).await {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected retry to be accepted: {}", error),
+    };
+
+    assert!(retry.response.is_none());
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::core::error::Error;
+
+pub const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(24u64 * 60u64 * 60u64);
+pub const DEFAULT_MAX_IDEMPOTENCY_ENTRIES: usize = 10_000usize;
+
+type StoredResponses = HashMap<String, (Instant, Result<Value, Error>)>;
+
+///
+/// Storage of the first response produced for each idempotency key.
+///
+#[async_trait]
+pub trait IdempotencyStore {
+    async fn get(&self, key: &str) -> Result<Option<Result<Value, Error>>, Error>;
+    async fn set(&self, key: &str, response: Result<Value, Error>) -> Result<(), Error>;
+}
+
+///
+/// In-memory store whose entries expire after a time to live. Once full, the expired entries are
+/// dropped first and then the oldest ones.
+///
+pub struct InMemoryIdempotencyStore {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<StoredResponses>,
+}
+
+impl InMemoryIdempotencyStore {
+    pub fn new(ttl: Duration, max_entries: usize) -> InMemoryIdempotencyStore {
+        InMemoryIdempotencyStore {
+            ttl,
+            max_entries: max_entries.max(1usize),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for InMemoryIdempotencyStore {
+    fn default() -> Self {
+        InMemoryIdempotencyStore::new(DEFAULT_IDEMPOTENCY_TTL, DEFAULT_MAX_IDEMPOTENCY_ENTRIES)
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    async fn get(&self, key: &str) -> Result<Option<Result<Value, Error>>, Error> {
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let is_expired = match entries.get(key) {
+            Some((stored_at, _)) => stored_at.elapsed() >= self.ttl,
+            None => return Ok(None),
+        };
+
+        if is_expired {
+            entries.remove(key);
+            return Ok(None);
+        }
+
+        Ok(entries.get(key).map(|(_, response)| response.clone()))
+    }
+
+    async fn set(&self, key: &str, response: Result<Value, Error>) -> Result<(), Error> {
+        let mut entries = match self.entries.lock() {
+            Ok(entries) => entries,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if !entries.contains_key(key) && entries.len() >= self.max_entries {
+            entries.retain(|_, (stored_at, _)| stored_at.elapsed() < self.ttl);
+
+            while entries.len() >= self.max_entries {
+                let oldest = match entries.iter().min_by_key(|(_, (stored_at, _))| *stored_at) {
+                    Some((oldest, _)) => oldest.clone(),
+                    None => break,
+                };
+
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(key.to_string(), (Instant::now(), response));
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+pub async fn drop_oldest_entries_once_full() {
+    let store = InMemoryIdempotencyStore::new(Duration::from_secs(60u64), 2usize);
+
+    for key in ["first", "second", "third"] {
+        store
+            .set(key, Ok(Value::String(key.to_string())))
+            .await
+            .expect("failed to store response");
+    }
+
+    assert_eq!(None, store.get("first").await.unwrap());
+    assert_eq!(
+        Some(Ok(Value::String("third".to_string()))),
+        store.get("third").await.unwrap()
+    );
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use jsonwebtoken::errors::ErrorKind as JwtErrorKind;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::de::DeserializeOwned;
+#[cfg(test)]
+use serde_json::json;
+use serde_json::Value;
+
+use crate::api::server::input::connection_authenticator::ConnectionAuthenticator;
+use crate::api::server::input::input_data::InputData;
+use crate::api::server::input::input_plugin::InputPlugin;
+#[cfg(test)]
+use crate::api::shared::request::Request;
+use crate::api::shared::request_header::RequestHeader;
+use crate::core::error::{Error, ErrorKind};
+use crate::core::secrets::secrets_manager::SecretsManager;
+
+pub const JWT_PLUGIN_ID: &str = "jwt";
+
+///
+/// Header extra under which the verified claims of the token are stored as JSON.
+///
+pub const JWT_CLAIMS_EXTRA_KEY: &str = "jwt_claims";
+
+const BEARER_PREFIX: &str = "Bearer ";
+
+///
+/// Plugin which validates the token of each request as a JWT and exposes its verified claims to
+/// the action through the `jwt_claims` header extra, see `verified_claims`.
+///
+pub struct JwtPlugin {
+    keys: HashMap<Algorithm, DecodingKey>,
+    audience: Vec<String>,
+    issuer: Vec<String>,
+    leeway: Duration,
+}
+
+impl JwtPlugin {
+    pub fn new() -> JwtPlugin {
+        JwtPlugin {
+            keys: HashMap::new(),
+            audience: Vec::new(),
+            issuer: Vec::new(),
+            leeway: Duration::ZERO,
+        }
+    }
+
+    pub fn with_hs256_secret(mut self, secret: &[u8]) -> JwtPlugin {
+        self.keys
+            .insert(Algorithm::HS256, DecodingKey::from_secret(secret));
+        self
+    }
+
+    ///
+    /// Accepts HS256 tokens signed with the shared secret stored under `secret_id`.
+    ///
+    pub fn with_hs256_secret_from(
+        self,
+        secrets_manager: &dyn SecretsManager,
+        secret_id: &str,
+    ) -> Result<JwtPlugin, Error> {
+        match secrets_manager.get(secret_id) {
+            Some(secret) => Ok(self.with_hs256_secret(secret.as_bytes())),
+            None => Err(Error::new(
+                ErrorKind::InitializationError,
+                format!("failed to find jwt secret '{}'", secret_id),
+            )),
+        }
+    }
+
+    pub fn with_rs256_public_key_pem(mut self, public_key_pem: &[u8]) -> Result<JwtPlugin, Error> {
+        let key = match DecodingKey::from_rsa_pem(public_key_pem) {
+            Ok(key) => key,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!("invalid RS256 public key: {}", error),
+                ))
+            }
+        };
+
+        self.keys.insert(Algorithm::RS256, key);
+        Ok(self)
+    }
+
+    pub fn with_es256_public_key_pem(mut self, public_key_pem: &[u8]) -> Result<JwtPlugin, Error> {
+        let key = match DecodingKey::from_ec_pem(public_key_pem) {
+            Ok(key) => key,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!("invalid ES256 public key: {}", error),
+                ))
+            }
+        };
+
+        self.keys.insert(Algorithm::ES256, key);
+        Ok(self)
+    }
+
+    ///
+    /// Audiences accepted within the `aud` claim, which is not checked when none is given.
+    ///
+    pub fn with_audience(mut self, audience: Vec<String>) -> JwtPlugin {
+        self.audience = audience;
+        self
+    }
+
+    ///
+    /// Issuers accepted within the `iss` claim, which is not checked when none is given.
+    ///
+    pub fn with_issuer(mut self, issuer: Vec<String>) -> JwtPlugin {
+        self.issuer = issuer;
+        self
+    }
+
+    ///
+    /// Clock skew tolerated when checking the `exp` and `nbf` claims.
+    ///
+    pub fn with_leeway(mut self, leeway: Duration) -> JwtPlugin {
+        self.leeway = leeway;
+        self
+    }
+
+    fn verify(&self, token: &str) -> Result<Value, Error> {
+        let token = token.strip_prefix(BEARER_PREFIX).unwrap_or(token);
+
+        if token.is_empty() {
+            return Err(Error::new(ErrorKind::UnauthorizedError, "missing token"));
+        }
+
+        let header = match jsonwebtoken::decode_header(token) {
+            Ok(header) => header,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::UnauthorizedError,
+                    format!("invalid token: {}", error),
+                ))
+            }
+        };
+
+        let key = match self.keys.get(&header.alg) {
+            Some(key) => key,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::UnauthorizedError,
+                    format!("token algorithm '{:?}' is not accepted", header.alg),
+                ))
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.leeway.as_secs();
+        validation.validate_nbf = true;
+        validation.validate_aud = !self.audience.is_empty();
+
+        if !self.audience.is_empty() {
+            validation.set_audience(&self.audience);
+        }
+
+        if !self.issuer.is_empty() {
+            validation.set_issuer(&self.issuer);
+        }
+
+        match jsonwebtoken::decode::<Value>(token, key, &validation) {
+            Ok(token_data) => Ok(token_data.claims),
+            Err(error) => {
+                let message = match error.kind() {
+                    JwtErrorKind::ExpiredSignature => "token has expired".to_string(),
+                    JwtErrorKind::ImmatureSignature => "token is not valid yet".to_string(),
+                    JwtErrorKind::InvalidAudience => "token audience is not accepted".to_string(),
+                    JwtErrorKind::InvalidIssuer => "token issuer is not accepted".to_string(),
+                    JwtErrorKind::InvalidSignature => "token signature is invalid".to_string(),
+                    JwtErrorKind::MissingRequiredClaim(claim) => {
+                        format!("token is missing the '{}' claim", claim)
+                    }
+                    _ => format!("invalid token: {}", error),
+                };
+
+                Err(Error::new(ErrorKind::UnauthorizedError, message))
+            }
+        }
+    }
+}
+
+impl Default for JwtPlugin {
+    fn default() -> Self {
+        JwtPlugin::new()
+    }
+}
+
+#[async_trait]
+impl InputPlugin for JwtPlugin {
+    fn id(&self) -> &str {
+        JWT_PLUGIN_ID
+    }
+
+    async fn handle_input_data(
+        &self,
+        mut input_data: InputData,
+    ) -> Result<InputData, (InputData, Error)> {
+        let claims = match self.verify(input_data.request.header().token()) {
+            Ok(claims) => claims,
+            Err(error) => return Err((input_data, error)),
+        };
+
+        input_data
+            .request
+            .mut_header()
+            .add_extra(JWT_CLAIMS_EXTRA_KEY.to_string(), claims.to_string());
+
+        Ok(input_data)
+    }
+}
+
+#[async_trait]
+impl ConnectionAuthenticator for JwtPlugin {
+    async fn authenticate(&self, token: &str) -> Result<(), Error> {
+        self.verify(token).map(|_| ())
+    }
+}
+
+///
+/// Claims verified by the `JwtPlugin` for the request. Actions which filter out the plugin must
+/// not rely on them, since the header extra could have been set by the caller.
+///
+pub fn verified_claims<ClaimsType: DeserializeOwned>(
+    header: &RequestHeader,
+) -> Result<ClaimsType, Error> {
+    let claims = match header.get_extra(JWT_CLAIMS_EXTRA_KEY) {
+        Some(claims) => claims,
+        None => {
+            return Err(Error::new(
+                ErrorKind::UnauthorizedError,
+                "request has no verified claims",
+            ))
+        }
+    };
+
+    match serde_json::from_str::<ClaimsType>(claims) {
+        Ok(claims) => Ok(claims),
+        Err(error) => Err(Error::new(
+            ErrorKind::UnauthorizedError,
+            format!("failed to deserialize claims: {}", error),
+        )),
+    }
+}
+
+#[cfg(test)]
+const TEST_SECRET: &[u8] = b"secret";
+
+#[cfg(test)]
+fn create_input_data(claims: Value) -> InputData {
+    let token = jsonwebtoken::encode(
+        &jsonwebtoken::Header::new(Algorithm::HS256),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(TEST_SECRET),
+    )
+    .expect("failed to encode token");
+
+    InputData::new(
+        Request::new(
+            RequestHeader::new("dummy".to_string(), format!("Bearer {}", token)),
+            Value::Null,
+        ),
+        std::sync::Arc::new(|_| Box::pin(async { Ok(()) })),
+    )
+}
+
+#[cfg(test)]
+fn now_in_seconds() -> u64 {
+    jsonwebtoken::get_current_timestamp()
+}
+
+#[tokio::test]
+pub async fn expose_claims_of_valid_token() {
+    let plugin = JwtPlugin::new()
+        .with_hs256_secret(TEST_SECRET)
+        .with_audience(vec!["cuplan".to_string()]);
+
+    let input_data = create_input_data(json!({
+        "sub": "user",
+        "aud": "cuplan",
+        "exp": now_in_seconds() + 60u64,
+    }));
+
+    let input_data = match plugin.handle_input_data(input_data).await {
+        Ok(input_data) => input_data,
+        Err((_, error)) => panic!("expected valid token: {}", error),
+    };
+
+    let claims: Value =
+        verified_claims(input_data.request.header()).expect("expected verified claims");
+
+    assert_eq!(Some(&json!("user")), claims.get("sub"));
+}
+
+#[tokio::test]
+pub async fn reject_expired_token() {
+    let plugin = JwtPlugin::new().with_hs256_secret(TEST_SECRET);
+
+    let input_data = create_input_data(json!({
+        "sub": "user",
+        "exp": now_in_seconds() - 600u64,
+    }));
+
+    let error = match plugin.handle_input_data(input_data).await {
+        Ok(_) => panic!("expected expired token to be rejected"),
+        Err((_, error)) => error,
+    };
+
+    assert_eq!(ErrorKind::UnauthorizedError, error.kind());
+    assert_eq!("token has expired", error.message);
+}
+
+#[tokio::test]
+pub async fn reject_token_of_other_issuer() {
+    let plugin = JwtPlugin::new()
+        .with_hs256_secret(TEST_SECRET)
+        .with_issuer(vec!["cuplan".to_string()]);
+
+    let input_data = create_input_data(json!({
+        "sub": "user",
+        "iss": "other",
+        "exp": now_in_seconds() + 60u64,
+    }));
+
+    let error = match plugin.handle_input_data(input_data).await {
+        Ok(_) => panic!("expected token of other issuer to be rejected"),
+        Err((_, error)) => error,
+    };
+
+    assert_eq!(ErrorKind::UnauthorizedError, error.kind());
+}
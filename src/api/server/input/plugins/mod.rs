@@ -1 +1,5 @@
-
+pub mod idempotency_plugin;
+pub mod idempotency_store;
+pub mod jwt_plugin;
+pub mod rate_limit_plugin;
+pub mod schema_validation_plugin;
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde_json::json;
+#[cfg(test)]
+use serde_json::Value;
+
+use crate::api::server::dispatch::requested_action_key;
+use crate::api::server::input::action::ACTION_VERSION_SEPARATOR;
+use crate::api::server::input::input_data::InputData;
+use crate::api::server::input::input_plugin::InputPlugin;
+#[cfg(test)]
+use crate::api::shared::request::Request;
+use crate::api::shared::request_header::RequestHeader;
+use crate::core::error::{Error, ErrorKind};
+
+pub const RATE_LIMIT_PLUGIN_ID: &str = "rate_limit";
+pub const DEFAULT_MAX_TRACKED_KEYS: usize = 10_000usize;
+
+const KEY_SEPARATOR: char = '\u{1f}';
+
+///
+/// Part of the request which identifies the caller whose requests are limited.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateLimitKey {
+    Token,
+    Action,
+    ///
+    /// Value of the given header extra, e.g. the tenant of the request.
+    ///
+    Extra(String),
+}
+
+///
+/// Amount of requests allowed within a period of time, bursts up to the whole capacity are
+/// allowed.
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RateLimit {
+    capacity: u32,
+    period: Duration,
+}
+
+impl RateLimit {
+    pub fn new(capacity: u32, period: Duration) -> RateLimit {
+        RateLimit {
+            capacity: capacity.max(1u32),
+            period,
+        }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn refill_per_second(&self) -> f64 {
+        if self.period.is_zero() {
+            return f64::INFINITY;
+        }
+
+        self.capacity as f64 / self.period.as_secs_f64()
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit, now: Instant) -> TokenBucket {
+        TokenBucket {
+            tokens: limit.capacity as f64,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * limit.refill_per_second())
+            .min(limit.capacity as f64);
+        self.last_refill = now;
+    }
+
+    ///
+    /// Takes a token from the bucket, otherwise returns how long until one is available.
+    ///
+    fn try_take(&mut self, limit: &RateLimit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+
+        if self.tokens >= 1f64 {
+            self.tokens -= 1f64;
+            return Ok(());
+        }
+
+        Err(Duration::from_secs_f64(
+            (1f64 - self.tokens) / limit.refill_per_second(),
+        ))
+    }
+
+    fn is_full(&self, limit: &RateLimit, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+
+        self.tokens + elapsed.as_secs_f64() * limit.refill_per_second() >= limit.capacity as f64
+    }
+}
+
+///
+/// Plugin which limits the requests of each caller through token buckets. Callers are told apart
+/// by the configured mix of token, action and header extras, whereas actions can have their own
+/// limits. Throttled requests are replied with a `RateLimitedError` which tells when to retry.
+///
+pub struct RateLimitPlugin {
+    keys: Vec<RateLimitKey>,
+    default_limit: RateLimit,
+    action_limits: HashMap<String, RateLimit>,
+    max_tracked_keys: usize,
+    buckets: Mutex<HashMap<String, (RateLimit, TokenBucket)>>,
+}
+
+impl RateLimitPlugin {
+    pub fn new(keys: Vec<RateLimitKey>, default_limit: RateLimit) -> RateLimitPlugin {
+        RateLimitPlugin {
+            keys,
+            default_limit,
+            action_limits: HashMap::new(),
+            max_tracked_keys: DEFAULT_MAX_TRACKED_KEYS,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// Limit applied to the given action instead of the default one, each caller has a bucket
+    /// per action with its own limit. The action is either a version, e.g. `create_org@v1`, or
+    /// an id whose limit applies to each of its versions without a limit of their own, each
+    /// version still having its own bucket.
+    ///
+    pub fn with_action_limit(mut self, action: &str, limit: RateLimit) -> RateLimitPlugin {
+        self.action_limits.insert(action.to_string(), limit);
+        self
+    }
+
+    ///
+    /// Maximum amount of buckets kept in memory. Once reached, full buckets are dropped first and
+    /// then the least recently used ones.
+    ///
+    pub fn with_max_tracked_keys(mut self, max_tracked_keys: usize) -> RateLimitPlugin {
+        self.max_tracked_keys = max_tracked_keys.max(1usize);
+        self
+    }
+
+    fn action_limit(&self, action_key: &str) -> Option<&RateLimit> {
+        match self.action_limits.get(action_key) {
+            Some(limit) => Some(limit),
+            None => {
+                let (id, _) = action_key.split_once(ACTION_VERSION_SEPARATOR)?;
+
+                self.action_limits.get(id)
+            }
+        }
+    }
+
+    fn bucket_key(&self, header: &RequestHeader, action_key: &str) -> String {
+        let mut parts: Vec<&str> = Vec::new();
+
+        // actions with their own limits never share buckets with other actions
+        if self.action_limit(action_key).is_some() {
+            parts.push(action_key);
+        }
+
+        for key in self.keys.iter() {
+            parts.push(match key {
+                RateLimitKey::Token => header.token(),
+                RateLimitKey::Action => action_key,
+                RateLimitKey::Extra(extra) => header
+                    .get_extra(extra)
+                    .map(|value| value.as_str())
+                    .unwrap_or_default(),
+            });
+        }
+
+        parts.join(&KEY_SEPARATOR.to_string())
+    }
+
+    fn try_acquire(&self, header: &RequestHeader) -> Result<(), Error> {
+        let action_key = requested_action_key(header);
+        let limit = *self
+            .action_limit(action_key.as_str())
+            .unwrap_or(&self.default_limit);
+        let key = self.bucket_key(header, action_key.as_str());
+        let now = Instant::now();
+
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if !buckets.contains_key(&key) && buckets.len() >= self.max_tracked_keys {
+            evict_buckets(&mut buckets, self.max_tracked_keys, now);
+        }
+
+        let (_, bucket) = buckets
+            .entry(key)
+            .or_insert_with(|| (limit, TokenBucket::new(&limit, now)));
+
+        match bucket.try_take(&limit, now) {
+            Ok(_) => Ok(()),
+            Err(retry_after) => {
+                let retry_after_milliseconds = retry_after.as_millis().max(1u128);
+
+                Err(Error::new(
+                    ErrorKind::RateLimitedError,
+                    format!(
+                        "rate limit exceeded, retry after {} milliseconds",
+                        retry_after_milliseconds
+                    ),
+                )
+                .with_details(json!({
+                    "retry_after_milliseconds": retry_after_milliseconds,
+                })))
+            }
+        }
+    }
+}
+
+fn evict_buckets(
+    buckets: &mut HashMap<String, (RateLimit, TokenBucket)>,
+    max_tracked_keys: usize,
+    now: Instant,
+) {
+    // full buckets behave as new ones, so dropping them loses no state
+    buckets.retain(|_, (limit, bucket)| !bucket.is_full(limit, now));
+
+    while buckets.len() >= max_tracked_keys {
+        let least_recently_used = match buckets
+            .iter()
+            .min_by_key(|(_, (_, bucket))| bucket.last_refill)
+        {
+            Some((key, _)) => key.clone(),
+            None => return,
+        };
+
+        buckets.remove(&least_recently_used);
+    }
+}
+
+#[async_trait]
+impl InputPlugin for RateLimitPlugin {
+    fn id(&self) -> &str {
+        RATE_LIMIT_PLUGIN_ID
+    }
+
+    async fn handle_input_data(
+        &self,
+        input_data: InputData,
+    ) -> Result<InputData, (InputData, Error)> {
+        match self.try_acquire(input_data.request.header()) {
+            Ok(_) => Ok(input_data),
+            Err(error) => Err((input_data, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+fn create_input_data(action: &str, token: &str) -> InputData {
+    InputData::new(
+        Request::new(
+            RequestHeader::new(action.to_string(), token.to_string()),
+            Value::Null,
+        ),
+        std::sync::Arc::new(|_| Box::pin(async { Ok(()) })),
+    )
+}
+
+#[tokio::test]
+pub async fn reject_requests_beyond_limit_with_retry_after() {
+    let plugin = RateLimitPlugin::new(
+        vec![RateLimitKey::Token],
+        RateLimit::new(2u32, Duration::from_secs(60u64)),
+    );
+
+    for _ in 0..2 {
+        assert!(plugin
+            .handle_input_data(create_input_data("create_org", "caller"))
+            .await
+            .is_ok());
+    }
+
+    let error = match plugin
+        .handle_input_data(create_input_data("create_org", "caller"))
+        .await
+    {
+        Ok(_) => panic!("expected request to be rate limited"),
+        Err((_, error)) => error,
+    };
+
+    assert_eq!(ErrorKind::RateLimitedError, error.kind());
+    let retry_after = error
+        .details()
+        .and_then(|details| details.get("retry_after_milliseconds"))
+        .and_then(|retry_after| retry_after.as_u64())
+        .expect("expected retry after milliseconds");
+    assert!(retry_after > 29_000u64 && retry_after <= 30_000u64);
+
+    assert!(plugin
+        .handle_input_data(create_input_data("create_org", "other_caller"))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+pub async fn apply_action_limits_independently() {
+    let plugin = RateLimitPlugin::new(
+        vec![RateLimitKey::Token],
+        RateLimit::new(1u32, Duration::from_secs(60u64)),
+    )
+    .with_action_limit(
+        "expensive",
+        RateLimit::new(1u32, Duration::from_secs(60u64)),
+    );
+
+    assert!(plugin
+        .handle_input_data(create_input_data("expensive", "caller"))
+        .await
+        .is_ok());
+    assert!(plugin
+        .handle_input_data(create_input_data("expensive", "caller"))
+        .await
+        .is_err());
+    assert!(plugin
+        .handle_input_data(create_input_data("cheap", "caller"))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+pub async fn bound_tracked_keys() {
+    let plugin = RateLimitPlugin::new(
+        vec![RateLimitKey::Token],
+        RateLimit::new(1u32, Duration::from_secs(60u64)),
+    )
+    .with_max_tracked_keys(2usize);
+
+    for caller in ["first", "second", "third"] {
+        assert!(plugin
+            .handle_input_data(create_input_data("create_org", caller))
+            .await
+            .is_ok());
+    }
+
+    assert_eq!(2usize, plugin.buckets.lock().unwrap().len());
+}
+
+#[tokio::test]
+pub async fn limit_each_action_version_independently() {
+    let plugin = RateLimitPlugin::new(
+        vec![RateLimitKey::Token, RateLimitKey::Action],
+        RateLimit::new(1u32, Duration::from_secs(60u64)),
+    )
+    .with_action_limit(
+        "create_org@v1",
+        RateLimit::new(2u32, Duration::from_secs(60u64)),
+    );
+
+    for _ in 0..2 {
+        assert!(plugin
+            .handle_input_data(create_input_data("create_org@v1", "caller"))
+            .await
+            .is_ok());
+    }
+    assert!(plugin
+        .handle_input_data(create_input_data("create_org@v1", "caller"))
+        .await
+        .is_err());
+
+    let header = RequestHeader::new("create_org".to_string(), "caller".to_string())
+        .with_version("v2".to_string());
+    let input_data = InputData::new(
+        Request::new(header, Value::Null),
+        std::sync::Arc::new(|_| Box::pin(async { Ok(()) })),
+    );
+
+    assert!(plugin.handle_input_data(input_data).await.is_ok());
+    assert!(plugin
+        .handle_input_data(create_input_data("create_org@v2", "caller"))
+        .await
+        .is_err());
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use async_trait::async_trait;
+use jsonschema::Validator;
+use schemars::JsonSchema;
+#[cfg(test)]
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::api::server::dispatch::requested_action_key;
+use crate::api::server::input::action::ACTION_VERSION_SEPARATOR;
+use crate::api::server::input::input_data::InputData;
+use crate::api::server::input::input_plugin::InputPlugin;
+#[cfg(test)]
+use crate::api::shared::request::Request;
+#[cfg(test)]
+use crate::api::shared::request_header::RequestHeader;
+use crate::core::error::{Error, ErrorKind};
+
+pub const SCHEMA_VALIDATION_PLUGIN_ID: &str = "schema_validation";
+
+///
+/// Plugin which validates the payload of each request against the JSON Schema registered for
+/// its action before the action is executed. Requests of actions without schema are not
+/// validated.
+///
+pub struct SchemaValidationPlugin {
+    validators: HashMap<String, Validator>,
+}
+
+impl SchemaValidationPlugin {
+    pub fn new() -> SchemaValidationPlugin {
+        SchemaValidationPlugin {
+            validators: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Registers the schema of an action, versioned actions are registered under their key, e.g.
+    /// `create_org@v2`. Schemas registered under the action's id apply to the versions without
+    /// schema of their own. Requests without version are validated against the schema of the
+    /// action's default version.
+    ///
+    pub fn with_schema(
+        mut self,
+        action: &str,
+        schema: &Value,
+    ) -> Result<SchemaValidationPlugin, Error> {
+        let validator = match jsonschema::validator_for(schema) {
+            Ok(validator) => validator,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!("invalid schema for action '{}': {}", action, error),
+                ))
+            }
+        };
+
+        self.validators.insert(action.to_string(), validator);
+
+        Ok(self)
+    }
+
+    pub fn with_schema_file(
+        self,
+        action: &str,
+        path: impl AsRef<Path>,
+    ) -> Result<SchemaValidationPlugin, Error> {
+        let content = match std::fs::read_to_string(path.as_ref()) {
+            Ok(content) => content,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!(
+                        "failed to read schema file '{}': {}",
+                        path.as_ref().display(),
+                        error
+                    ),
+                ))
+            }
+        };
+
+        let schema = match serde_json::from_str::<Value>(content.as_str()) {
+            Ok(schema) => schema,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!(
+                        "failed to deserialize schema file '{}': {}",
+                        path.as_ref().display(),
+                        error
+                    ),
+                ))
+            }
+        };
+
+        self.with_schema(action, &schema)
+    }
+
+    ///
+    /// Registers the schema derived from the action's payload type.
+    ///
+    pub fn with_schema_for<PayloadType: JsonSchema>(
+        self,
+        action: &str,
+    ) -> Result<SchemaValidationPlugin, Error> {
+        let schema = match serde_json::to_value(schemars::schema_for!(PayloadType)) {
+            Ok(schema) => schema,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!(
+                        "failed to serialize schema of action '{}': {}",
+                        action, error
+                    ),
+                ))
+            }
+        };
+
+        self.with_schema(action, &schema)
+    }
+
+    fn validator(&self, action_key: &str) -> Option<&Validator> {
+        match self.validators.get(action_key) {
+            Some(validator) => Some(validator),
+            None => {
+                let (id, _) = action_key.split_once(ACTION_VERSION_SEPARATOR)?;
+
+                self.validators.get(id)
+            }
+        }
+    }
+
+    fn validate(&self, action: &str, payload: &Value) -> Result<(), Error> {
+        let validator = match self.validator(action) {
+            Some(validator) => validator,
+            None => return Ok(()),
+        };
+
+        let violations: Vec<Value> = validator
+            .iter_errors(payload)
+            .map(|violation| {
+                json!({
+                    "path": violation.instance_path.to_string(),
+                    "message": violation.to_string(),
+                })
+            })
+            .collect();
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(Error::new(
+            ErrorKind::ValidationError,
+            format!(
+                "payload of action '{}' has {} schema violations",
+                action,
+                violations.len()
+            ),
+        )
+        .with_details(json!({ "violations": violations })))
+    }
+}
+
+impl Default for SchemaValidationPlugin {
+    fn default() -> Self {
+        SchemaValidationPlugin::new()
+    }
+}
+
+#[async_trait]
+impl InputPlugin for SchemaValidationPlugin {
+    fn id(&self) -> &str {
+        SCHEMA_VALIDATION_PLUGIN_ID
+    }
+
+    async fn handle_input_data(
+        &self,
+        input_data: InputData,
+    ) -> Result<InputData, (InputData, Error)> {
+        let action = requested_action_key(input_data.request.header());
+
+        match self.validate(&action, input_data.request.payload()) {
+            Ok(_) => Ok(input_data),
+            Err(error) => Err((input_data, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateOrgPayload {
+    pub name: String,
+    pub members: u32,
+}
+
+#[cfg(test)]
+fn create_input_data(action: &str, payload: Value) -> InputData {
+    InputData::new(
+        Request::new(
+            RequestHeader::new(action.to_string(), "".to_string()),
+            payload,
+        ),
+        std::sync::Arc::new(|_| Box::pin(async { Ok(()) })),
+    )
+}
+
+#[tokio::test]
+pub async fn reply_with_every_schema_violation() {
+    let plugin = SchemaValidationPlugin::new()
+        .with_schema_for::<CreateOrgPayload>("create_org")
+        .expect("failed to register schema");
+
+    let input_data = create_input_data("create_org", json!({ "members": "many" }));
+
+    let error = match plugin.handle_input_data(input_data).await {
+        Ok(_) => panic!("expected invalid payload to be rejected"),
+        Err((_, error)) => error,
+    };
+
+    assert_eq!(ErrorKind::ValidationError, error.kind());
+
+    let violations = error
+        .details()
+        .and_then(|details| details.get("violations"))
+        .and_then(|violations| violations.as_array())
+        .expect("expected violations");
+    let paths: Vec<&str> = violations
+        .iter()
+        .filter_map(|violation| violation.get("path").and_then(|path| path.as_str()))
+        .collect();
+
+    assert_eq!(2usize, violations.len());
+    assert!(paths.contains(&""));
+    assert!(paths.contains(&"/members"));
+}
+
+#[tokio::test]
+pub async fn accept_valid_payload_and_actions_without_schema() {
+    let plugin = SchemaValidationPlugin::new()
+        .with_schema(
+            "create_org",
+            &json!({
+                "type": "object",
+                "required": ["name"],
+                "properties": { "name": { "type": "string" } }
+            }),
+        )
+        .expect("failed to register schema");
+
+    assert!(plugin
+        .handle_input_data(create_input_data("create_org", json!({ "name": "org" })))
+        .await
+        .is_ok());
+    assert!(plugin
+        .handle_input_data(create_input_data("delete_org", json!(1)))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+pub async fn validate_against_schema_of_requested_version() {
+    let plugin = SchemaValidationPlugin::new()
+        .with_schema(
+            "create_org",
+            &json!({ "type": "object", "required": ["name"] }),
+        )
+        .expect("failed to register schema")
+        .with_schema(
+            "create_org@v2",
+            &json!({ "type": "object", "required": ["name", "members"] }),
+        )
+        .expect("failed to register schema");
+
+    let versioned_input_data = |version: &str| {
+        InputData::new(
+            Request::new(
+                RequestHeader::new("create_org".to_string(), "".to_string())
+                    .with_version(version.to_string()),
+                json!({ "name": "org" }),
+            ),
+            std::sync::Arc::new(|_| Box::pin(async { Ok(()) })),
+        )
+    };
+
+    // the dispatch resolves requests without version to the default version, e.g. v2
+    assert!(plugin
+        .handle_input_data(versioned_input_data("v2"))
+        .await
+        .is_err());
+    assert!(plugin
+        .handle_input_data(versioned_input_data("v1"))
+        .await
+        .is_ok());
+}
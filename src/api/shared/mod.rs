@@ -1,2 +1,3 @@
 pub mod request;
 pub mod request_header;
+pub mod response;
@@ -3,7 +3,7 @@ use serde_json::Value;
 
 use crate::api::shared::request_header::RequestHeader;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Request {
     header: RequestHeader,
     payload: Value,
@@ -2,11 +2,15 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct RequestHeader {
     action: String,
     token: String,
     extra: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
 }
 
 impl RequestHeader {
@@ -15,21 +19,68 @@ impl RequestHeader {
             action,
             token,
             extra: HashMap::new(),
+            version: None,
+            id: None,
         }
     }
 
+    ///
+    /// Requests a specific version of the action, otherwise the action's default version is
+    /// used.
+    ///
+    pub fn with_version(mut self, version: String) -> RequestHeader {
+        self.version = Some(version);
+        self
+    }
+
+    ///
+    /// Identifier of the request, which is sent back within the response.
+    ///
+    pub fn with_id(mut self, id: String) -> RequestHeader {
+        self.id = Some(id);
+        self
+    }
+
     pub fn action(&self) -> &str {
         self.action.as_str()
     }
 
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
     pub fn token(&self) -> &str {
         self.token.as_str()
     }
 
+    ///
+    /// Replaces the token, e.g. with the one authenticated for the connection the request was
+    /// received through.
+    ///
+    pub fn set_token(&mut self, token: String) {
+        self.token = token;
+    }
+
+    ///
+    /// Replaces the requested version, e.g. with the default version of the action when none was
+    /// requested.
+    ///
+    pub fn set_version(&mut self, version: String) {
+        self.version = Some(version);
+    }
+
     pub fn add_extra(&mut self, key: String, value: String) -> Option<String> {
         self.extra.insert(key, value)
     }
 
+    pub fn remove_extra(&mut self, key: &str) -> Option<String> {
+        self.extra.remove(key)
+    }
+
     pub fn has_extra(&self, key: &str) -> bool {
         self.extra.contains_key(key)
     }
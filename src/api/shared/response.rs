@@ -0,0 +1,265 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::core::error::{Error, ErrorKind};
+
+///
+/// Version of the response envelope, increased whenever its layout changes in a non-compatible
+/// way.
+///
+pub const RESPONSE_VERSION: u32 = 1u32;
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus {
+    Ok,
+    Error,
+}
+
+///
+/// Layout of the replies sent through the API.
+///
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum ResponseFormat {
+    ///
+    /// Replies are sent as a `Response` envelope.
+    ///
+    #[default]
+    Envelope,
+    ///
+    /// Replies are sent as the serialized action result, `{"Ok": ...}` or `{"Err": {...}}`, for
+    /// the clients which have not migrated yet to the envelope.
+    ///
+    Legacy,
+}
+
+///
+/// Envelope of the replies sent through the API. The payload is set when the status is `ok`,
+/// whereas the error is set when the status is `error`.
+///
+/// ```json
+/// {
+///     "version": 1,
+///     "status": "ok",
+///     "payload": { "id": "..." },
+///     "request_id": "...",
+///     "processing_time_milliseconds": 12
+/// }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    version: u32,
+    status: ResponseStatus,
+    #[serde(default)]
+    payload: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<Error>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(default)]
+    processing_time_milliseconds: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+}
+
+impl Response {
+    pub fn new(result: Result<Value, Error>) -> Response {
+        let (status, payload, error) = match result {
+            Ok(payload) => (ResponseStatus::Ok, payload, None),
+            Err(error) => (ResponseStatus::Error, Value::Null, Some(error)),
+        };
+
+        Response {
+            version: RESPONSE_VERSION,
+            status,
+            payload,
+            error,
+            request_id: None,
+            processing_time_milliseconds: 0u64,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: Option<String>) -> Response {
+        self.request_id = request_id;
+        self
+    }
+
+    pub fn with_processing_time(mut self, processing_time: Duration) -> Response {
+        self.processing_time_milliseconds =
+            u64::try_from(processing_time.as_millis()).unwrap_or(u64::MAX);
+        self
+    }
+
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Response {
+        self.warnings = warnings;
+        self
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn status(&self) -> ResponseStatus {
+        self.status
+    }
+
+    pub fn payload(&self) -> &Value {
+        &self.payload
+    }
+
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    pub fn processing_time(&self) -> Duration {
+        Duration::from_millis(self.processing_time_milliseconds)
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        self.warnings.as_slice()
+    }
+
+    pub fn into_result(self) -> Result<Value, Error> {
+        match self.status {
+            ResponseStatus::Ok => Ok(self.payload),
+            ResponseStatus::Error => match self.error {
+                Some(error) => Err(error),
+                None => Err(Error::new(
+                    ErrorKind::ApiError,
+                    "received an error response without error",
+                )),
+            },
+        }
+    }
+}
+
+///
+/// Serializes the result of a request into the given reply format.
+///
+pub fn encode_response(response: Response, response_format: ResponseFormat) -> Value {
+    match response_format {
+        ResponseFormat::Envelope => match serde_json::to_value(&response) {
+            Ok(value) => value,
+            Err(error) => json!({
+                "version": RESPONSE_VERSION,
+                "status": ResponseStatus::Error,
+                "error": Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to serialize response: {}", error)
+                ),
+            }),
+        },
+        // the legacy layout has no room for the warnings, as any other key fails the
+        // deserialization of the clients which read it as a `Result`
+        ResponseFormat::Legacy => json!(response.into_result()),
+    }
+}
+
+///
+/// Decodes a reply received through the API, either a `Response` envelope or the legacy
+/// `{"Ok": ...}` / `{"Err": {...}}` layout, into the result of the request.
+///
+pub fn decode_response(value: Value) -> Result<Value, Error> {
+    let is_envelope = value
+        .as_object()
+        .map(|object| object.contains_key("version") && object.contains_key("status"))
+        .unwrap_or(false);
+
+    if is_envelope {
+        return match serde_json::from_value::<Response>(value) {
+            Ok(response) => response.into_result(),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to deserialize response: {}", error),
+            )),
+        };
+    }
+
+    let mut object = match value {
+        Value::Object(object) => object,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                "received a response with an unknown layout",
+            ))
+        }
+    };
+
+    if let Some(ok_value) = object.remove("Ok") {
+        return Ok(ok_value);
+    }
+
+    match object.remove("Err") {
+        Some(error_value) => match serde_json::from_value::<Error>(error_value) {
+            Ok(error) => Err(error),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to deserialize error: {}", error),
+            )),
+        },
+        None => Err(Error::new(
+            ErrorKind::ApiError,
+            "received a response with an unknown layout",
+        )),
+    }
+}
+
+#[test]
+pub fn decode_envelope_response() {
+    let response = Response::new(Err(Error::new(ErrorKind::RequestError, "invalid payload")))
+        .with_request_id(Some("1".to_string()))
+        .with_processing_time(Duration::from_millis(12u64));
+
+    let value = encode_response(response, ResponseFormat::Envelope);
+
+    assert_eq!(
+        json!({
+            "version": RESPONSE_VERSION,
+            "status": "error",
+            "payload": null,
+            "error": { "kind": "RequestError", "message": "invalid payload" },
+            "request_id": "1",
+            "processing_time_milliseconds": 12
+        }),
+        value
+    );
+    assert_eq!(
+        Err(Error::new(ErrorKind::RequestError, "invalid payload")),
+        decode_response(value)
+    );
+}
+
+#[test]
+pub fn decode_legacy_response() {
+    let response = Response::new(Ok(json!("expected")))
+        .with_warnings(vec!["action 'x@v1' is deprecated".to_string()]);
+
+    let value = encode_response(response, ResponseFormat::Legacy);
+
+    assert_eq!(json!({ "Ok": "expected" }), value);
+    assert_eq!(Ok(json!("expected")), decode_response(value));
+}
+
+#[test]
+pub fn legacy_response_is_a_plain_result() {
+    let response = Response::new(Err(Error::new(ErrorKind::RequestError, "invalid payload")))
+        .with_warnings(vec!["action 'x@v1' is deprecated".to_string()]);
+
+    let value = encode_response(response, ResponseFormat::Legacy);
+
+    let result = serde_json::from_value::<Result<Value, Error>>(value)
+        .expect("legacy response is not a result");
+
+    assert_eq!(
+        Err(Error::new(ErrorKind::RequestError, "invalid payload")),
+        result
+    );
+}
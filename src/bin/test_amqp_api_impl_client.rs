@@ -90,10 +90,6 @@ pub async fn main() {
     );
 
     let response: Value = amqp_input_consumer.send_request(request).await.unwrap();
-    let response_object = response.as_object().unwrap();
 
-    let response_ok = response_object.get("Ok").unwrap();
-    let response_string = response_ok.as_str().unwrap();
-
-    assert_eq!("expected", response_string);
+    assert_eq!(json!("expected"), response);
 }
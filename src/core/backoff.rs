@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+///
+/// Exponential backoff with jitter between the attempts of an operation, e.g. reconnecting to a
+/// broker. Each delay doubles the previous one up to the maximum, and half of it is random so
+/// the clients which failed at the same time do not retry at the same time.
+///
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Backoff {
+        Backoff {
+            initial,
+            max,
+            attempt: 0u32,
+        }
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let exponential = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(self.attempt))
+            .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+
+        let half = exponential / 2;
+
+        half + random_duration(exponential - half)
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0u32;
+    }
+}
+
+fn random_duration(max: Duration) -> Duration {
+    let max_nanoseconds = u64::try_from(max.as_nanos()).unwrap_or(u64::MAX);
+
+    if max_nanoseconds == 0u64 {
+        return Duration::ZERO;
+    }
+
+    let random = uuid::Uuid::new_v4().as_u128() as u64;
+
+    Duration::from_nanos(random % max_nanoseconds)
+}
+
+#[test]
+pub fn double_delay_up_to_max_with_jitter() {
+    let mut backoff = Backoff::new(
+        Duration::from_millis(100u64),
+        Duration::from_millis(1000u64),
+    );
+
+    let expected_bounds = [100u64, 200u64, 400u64, 800u64, 1000u64, 1000u64];
+
+    for expected_bound in expected_bounds {
+        let delay = backoff.next_delay();
+
+        assert!(delay >= Duration::from_millis(expected_bound / 2));
+        assert!(delay <= Duration::from_millis(expected_bound));
+    }
+
+    backoff.reset();
+
+    assert!(backoff.next_delay() <= Duration::from_millis(100u64));
+}
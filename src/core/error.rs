@@ -2,6 +2,7 @@ use std::fmt;
 
 use async_channel::{RecvError, SendError};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tokio::time::error::Elapsed;
 
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
@@ -13,12 +14,36 @@ pub enum ErrorKind {
     Unknown,
     InitializationError,
     InternalError,
+    UnknownActionError,
+    TimeoutError,
+    OverloadedError,
+    UnauthorizedError,
+    RateLimitedError,
+    ValidationError,
+}
+
+impl ErrorKind {
+    ///
+    /// Whether the error may go away by retrying, e.g. a timeout or an overloaded service.
+    ///
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::TimeoutError
+                | ErrorKind::OverloadedError
+                | ErrorKind::RateLimitedError
+                | ErrorKind::StorageError
+                | ErrorKind::InternalError
+        )
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Error {
     pub kind: ErrorKind,
     pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    details: Option<Value>,
 }
 
 impl Error {
@@ -26,12 +51,25 @@ impl Error {
         Error {
             kind,
             message: message.into(),
+            details: None,
         }
     }
 
+    ///
+    /// Attaches machine readable information about the error, which is sent along the message.
+    ///
+    pub fn with_details(mut self, details: Value) -> Error {
+        self.details = Some(details);
+        self
+    }
+
     pub fn kind(&self) -> ErrorKind {
         self.kind
     }
+
+    pub fn details(&self) -> Option<&Value> {
+        self.details.as_ref()
+    }
 }
 
 impl fmt::Display for Error {
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+static HEALTH: Lazy<Health> = Lazy::new(Health::new);
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    Up,
+    ///
+    /// The component lost its connection and is trying to get it back.
+    ///
+    Reconnecting,
+    Down,
+}
+
+///
+/// State of the components the service depends on, e.g. its broker connection. The service is
+/// healthy while all of them are up.
+///
+pub struct Health {
+    states: RwLock<BTreeMap<String, HealthState>>,
+}
+
+impl Health {
+    pub fn new() -> Health {
+        Health {
+            states: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn set_state(&self, component: &str, state: HealthState) {
+        let mut states = match self.states.write() {
+            Ok(states) => states,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        states.insert(component.to_string(), state);
+    }
+
+    pub fn state(&self, component: &str) -> Option<HealthState> {
+        let states = match self.states.read() {
+            Ok(states) => states,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        states.get(component).copied()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        let states = match self.states.read() {
+            Ok(states) => states,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        states.values().all(|state| *state == HealthState::Up)
+    }
+
+    ///
+    /// Overall status along the state of each component:
+    ///
+    /// ```json
+    /// { "status": "down", "components": { "amqp": "reconnecting" } }
+    /// ```
+    ///
+    pub fn report(&self) -> Value {
+        let states = match self.states.read() {
+            Ok(states) => states,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let status = if states.values().all(|state| *state == HealthState::Up) {
+            HealthState::Up
+        } else {
+            HealthState::Down
+        };
+
+        json!({
+            "status": status,
+            "components": *states,
+        })
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health::new()
+    }
+}
+
+pub fn health() -> &'static Health {
+    &HEALTH
+}
+
+#[test]
+pub fn report_down_while_any_component_is_not_up() {
+    let health = Health::new();
+
+    assert!(health.is_healthy());
+
+    health.set_state("amqp", HealthState::Up);
+    health.set_state("storage", HealthState::Reconnecting);
+
+    assert!(!health.is_healthy());
+    assert_eq!(
+        json!({
+            "status": "down",
+            "components": { "amqp": "up", "storage": "reconnecting" }
+        }),
+        health.report()
+    );
+
+    health.set_state("storage", HealthState::Up);
+
+    assert!(health.is_healthy());
+    assert_eq!(Some(HealthState::Up), health.state("storage"));
+}
@@ -0,0 +1,258 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use serde_json::Value;
+
+use crate::core::error::{Error, ErrorKind};
+
+///
+/// Label used for the requests of actions which are not registered, so unknown actions do not
+/// create new time series.
+///
+pub const UNKNOWN_ACTION_LABEL: &str = "unknown";
+
+static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+///
+/// Metrics of the API, logic and storage layers, registered within their own registry.
+///
+pub struct Metrics {
+    registry: Registry,
+    api_requests: IntCounterVec,
+    api_request_duration: HistogramVec,
+    api_errors: IntCounterVec,
+    api_plugin_rejections: IntCounterVec,
+    api_in_flight_requests: IntGauge,
+    executor_duration: HistogramVec,
+    executor_errors: IntCounterVec,
+    queue_depth: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let registry = Registry::new_custom(Some("cp_microservice".to_string()), None)
+            .expect("failed to create metrics registry");
+
+        let api_requests = IntCounterVec::new(
+            Opts::new("api_requests_total", "Requests handled per action"),
+            &["action", "status"],
+        )
+        .expect("failed to create api_requests_total metric");
+        let api_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "api_request_duration_seconds",
+                "Time from the reception of a request until its reply per action",
+            ),
+            &["action"],
+        )
+        .expect("failed to create api_request_duration_seconds metric");
+        let api_errors = IntCounterVec::new(
+            Opts::new(
+                "api_errors_total",
+                "Error replies per action and error kind",
+            ),
+            &["action", "kind"],
+        )
+        .expect("failed to create api_errors_total metric");
+        let api_plugin_rejections = IntCounterVec::new(
+            Opts::new(
+                "api_plugin_rejections_total",
+                "Requests rejected per plugin and error kind",
+            ),
+            &["plugin", "kind"],
+        )
+        .expect("failed to create api_plugin_rejections_total metric");
+        let api_in_flight_requests = IntGauge::new(
+            "api_in_flight_requests",
+            "Requests being handled by the API inputs",
+        )
+        .expect("failed to create api_in_flight_requests metric");
+        let executor_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "executor_duration_seconds",
+                "Execution time of the logic and storage executors per request variant",
+            ),
+            &["layer", "request"],
+        )
+        .expect("failed to create executor_duration_seconds metric");
+        let executor_errors = IntCounterVec::new(
+            Opts::new(
+                "executor_errors_total",
+                "Errors returned by the logic and storage executors per request variant",
+            ),
+            &["layer", "request"],
+        )
+        .expect("failed to create executor_errors_total metric");
+        let queue_depth = IntGaugeVec::new(
+            Opts::new(
+                "queue_depth",
+                "Requests waiting within the channels between layers",
+            ),
+            &["channel"],
+        )
+        .expect("failed to create queue_depth metric");
+
+        for collector in [
+            Box::new(api_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(api_request_duration.clone()),
+            Box::new(api_errors.clone()),
+            Box::new(api_plugin_rejections.clone()),
+            Box::new(api_in_flight_requests.clone()),
+            Box::new(executor_duration.clone()),
+            Box::new(executor_errors.clone()),
+            Box::new(queue_depth.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("failed to register metric");
+        }
+
+        Metrics {
+            registry,
+            api_requests,
+            api_request_duration,
+            api_errors,
+            api_plugin_rejections,
+            api_in_flight_requests,
+            executor_duration,
+            executor_errors,
+            queue_depth,
+        }
+    }
+
+    pub fn record_api_request(
+        &self,
+        action: &str,
+        result: &Result<Value, Error>,
+        elapsed: Duration,
+    ) {
+        let status = match result {
+            Ok(_) => "ok",
+            Err(error) => {
+                self.api_errors
+                    .with_label_values(&[action, error_kind_label(error.kind()).as_str()])
+                    .inc();
+                "error"
+            }
+        };
+
+        self.api_requests.with_label_values(&[action, status]).inc();
+        self.api_request_duration
+            .with_label_values(&[action])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_plugin_rejection(&self, plugin: &str, error: &Error) {
+        self.api_plugin_rejections
+            .with_label_values(&[plugin, error_kind_label(error.kind()).as_str()])
+            .inc();
+    }
+
+    pub fn add_api_in_flight_requests(&self, amount: i64) {
+        self.api_in_flight_requests.add(amount);
+    }
+
+    pub fn record_executor(&self, layer: &str, request: &str, failed: bool, elapsed: Duration) {
+        self.executor_duration
+            .with_label_values(&[layer, request])
+            .observe(elapsed.as_secs_f64());
+
+        if failed {
+            self.executor_errors
+                .with_label_values(&[layer, request])
+                .inc();
+        }
+    }
+
+    pub fn set_queue_depth(&self, channel: &str, depth: usize) {
+        self.queue_depth
+            .with_label_values(&[channel])
+            .set(i64::try_from(depth).unwrap_or(i64::MAX));
+    }
+
+    ///
+    /// Metrics in the Prometheus text exposition format.
+    ///
+    pub fn encode(&self) -> Result<String, Error> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        if let Err(error) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            return Err(Error::new(
+                ErrorKind::InternalError,
+                format!("failed to encode metrics: {}", error),
+            ));
+        }
+
+        match String::from_utf8(buffer) {
+            Ok(text) => Ok(text),
+            Err(error) => Err(Error::new(
+                ErrorKind::InternalError,
+                format!("failed to encode metrics: {}", error),
+            )),
+        }
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+fn error_kind_label(kind: ErrorKind) -> String {
+    format!("{:?}", kind)
+}
+
+///
+/// Name of the enum variant of a request, e.g. `CreateOrg` for `CreateOrg(...)`, used to label
+/// the metrics of each discriminant.
+///
+pub fn variant_name<RequestType: Debug>(request: &RequestType) -> String {
+    let debug = format!("{:?}", request);
+
+    debug
+        .split(|character: char| !(character.is_alphanumeric() || character == '_'))
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+pub enum MetricsTestRequest {
+    CreateOrg(String),
+    DeleteOrg { id: u32 },
+}
+
+#[test]
+pub fn name_variants_of_requests() {
+    assert_eq!(
+        "CreateOrg",
+        variant_name(&MetricsTestRequest::CreateOrg("org".to_string()))
+    );
+    assert_eq!(
+        "DeleteOrg",
+        variant_name(&MetricsTestRequest::DeleteOrg { id: 1u32 })
+    );
+}
+
+#[test]
+pub fn encode_recorded_metrics() {
+    metrics().record_api_request(
+        "metrics_test_action",
+        &Err(Error::new(ErrorKind::RequestError, "invalid payload")),
+        Duration::from_millis(5u64),
+    );
+
+    let text = metrics().encode().expect("failed to encode metrics");
+
+    assert!(text.contains(
+        "cp_microservice_api_requests_total{action=\"metrics_test_action\",status=\"error\"} 1"
+    ));
+    assert!(text.contains(
+        "cp_microservice_api_errors_total{action=\"metrics_test_action\",kind=\"RequestError\"} 1"
+    ));
+}
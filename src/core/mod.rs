@@ -1,3 +1,7 @@
+pub mod backoff;
 pub mod error;
 pub mod geolocalization;
+pub mod health;
+pub mod metrics;
 pub mod secrets;
+pub mod trace_context;
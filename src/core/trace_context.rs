@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use tracing::Span;
+
+///
+/// Span of the request which created a logic or storage request. Embedding it within the
+/// request types lets the logic and storage dispatchers continue the request's trace on the
+/// other side of the channel, see `SpanExtractor`.
+///
+#[derive(Clone, Debug)]
+pub struct TraceContext {
+    span: Span,
+}
+
+impl TraceContext {
+    ///
+    /// Captures the span in which the request is being created, e.g. the API request's span
+    /// within an action's executor.
+    ///
+    pub fn current() -> TraceContext {
+        TraceContext {
+            span: Span::current(),
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        TraceContext::current()
+    }
+}
+
+///
+/// Extracts from a request the span under which its handling is traced.
+///
+pub type SpanExtractor<RequestType> = Arc<dyn Fn(&RequestType) -> Option<Span> + Send + Sync>;
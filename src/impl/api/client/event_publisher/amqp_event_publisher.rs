@@ -0,0 +1,193 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use lapin::protocol::basic::AMQPProperties;
+use lapin::publisher_confirm::Confirmation;
+use lapin::types::ShortString;
+use lapin::Channel;
+use tokio::time::timeout;
+
+use crate::api::client::event_publisher::event_publisher::{Event, EventPublisher};
+use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::shared::amqp_event_publish::AmqpEventPublish;
+use crate::r#impl::api::shared::amqp_exchange::try_declare_exchange;
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+///
+/// Publishes events, serialized as JSON, to the configured exchange. Each event gets a unique
+/// message id and the timestamp at which it was published.
+///
+pub struct AmqpEventPublisher {
+    channel: Arc<Channel>,
+    publish: AmqpEventPublish,
+    timeout_after: Duration,
+}
+
+impl AmqpEventPublisher {
+    ///
+    /// Declares the configured exchange, unless it is the default one, whose name is empty.
+    ///
+    pub async fn try_new(
+        channel: Arc<Channel>,
+        publish: AmqpEventPublish,
+        timeout_after_milliseconds: u64,
+    ) -> Result<AmqpEventPublisher, Error> {
+        let timeout_after = Duration::from_millis(timeout_after_milliseconds);
+
+        match timeout(
+            timeout_after,
+            try_declare_exchange(&channel, publish.exchange()),
+        )
+        .await
+        {
+            Ok(result) => result?,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("timed out declaring exchange: {}", error),
+                ));
+            }
+        }
+
+        Ok(AmqpEventPublisher {
+            channel,
+            publish,
+            timeout_after,
+        })
+    }
+}
+
+#[async_trait]
+impl EventPublisher for AmqpEventPublisher {
+    async fn publish<EventType: Event + Send + Sync>(
+        &self,
+        event: &EventType,
+    ) -> Result<(), Error> {
+        let payload = match serde_json::to_vec(event) {
+            Ok(payload) => payload,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::RequestError,
+                    format!("failed to serialize event: {}", error),
+                ));
+            }
+        };
+
+        let routing_key = event.routing_key();
+        let properties = event_properties(self.publish.properties());
+
+        let confirm = match timeout(
+            self.timeout_after,
+            self.channel.basic_publish(
+                self.publish.exchange().name(),
+                routing_key.as_str(),
+                *self.publish.options(),
+                payload.as_slice(),
+                properties,
+            ),
+        )
+        .await
+        {
+            Ok(result) => match result {
+                Ok(confirm) => confirm,
+                Err(error) => {
+                    return Err(Error::new(
+                        ErrorKind::ApiError,
+                        format!("failed to publish event: {}", error),
+                    ));
+                }
+            },
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("timed out publishing event: {}", error),
+                ));
+            }
+        };
+
+        // the confirmation is only awaited by the broker when the channel is in confirm mode
+        match timeout(self.timeout_after, confirm).await {
+            Ok(result) => match result {
+                Ok(Confirmation::Nack(_)) => Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("broker did not accept event '{}'", routing_key),
+                )),
+                Ok(_) => Ok(()),
+                Err(error) => Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to confirm event: {}", error),
+                )),
+            },
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("timed out confirming event: {}", error),
+            )),
+        }
+    }
+}
+
+///
+/// Configured properties along the content type, a unique message id and the current timestamp.
+///
+fn event_properties(properties: &AMQPProperties) -> AMQPProperties {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+
+    properties
+        .clone()
+        .with_content_type(ShortString::from(JSON_CONTENT_TYPE))
+        .with_message_id(ShortString::from(uuid::Uuid::new_v4().to_string()))
+        .with_timestamp(timestamp)
+}
+
+#[test]
+pub fn set_message_id_and_timestamp_of_each_event() {
+    let configured_properties =
+        AMQPProperties::default().with_app_id(ShortString::from("cp-organization"));
+
+    let first = event_properties(&configured_properties);
+    let second = event_properties(&configured_properties);
+
+    assert_eq!(
+        Some(&ShortString::from("cp-organization")),
+        first.app_id().as_ref()
+    );
+    assert_eq!(
+        Some(&ShortString::from(JSON_CONTENT_TYPE)),
+        first.content_type().as_ref()
+    );
+    assert!(first.timestamp().is_some());
+    assert!(first.message_id().is_some());
+    assert_ne!(first.message_id(), second.message_id());
+}
+
+#[test]
+pub fn deserialize_event_publish_with_default_properties() {
+    let publish: AmqpEventPublish = serde_json::from_str(
+        r#"{
+            "exchange": {
+                "name": "organization_events",
+                "kind": "Topic",
+                "declare": {
+                    "options": {
+                        "passive": false,
+                        "durable": true,
+                        "auto_delete": false,
+                        "internal": false,
+                        "nowait": false
+                    },
+                    "arguments": {}
+                }
+            }
+        }"#,
+    )
+    .expect("failed to deserialize event publish");
+
+    assert_eq!("organization_events", publish.exchange().name());
+    assert_eq!(&lapin::ExchangeKind::Topic, publish.exchange().kind());
+    assert!(publish.exchange().declare().options().durable);
+}
@@ -0,0 +1 @@
+pub mod amqp_event_publisher;
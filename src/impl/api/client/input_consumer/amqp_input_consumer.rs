@@ -1,22 +1,71 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use async_trait::async_trait;
-use futures_util::TryStreamExt;
-use lapin::types::ShortString;
-use lapin::Channel;
+use futures_util::StreamExt;
+use lapin::options::{BasicAckOptions, BasicCancelOptions, BasicConsumeOptions};
+use lapin::types::{FieldTable, ShortString};
+use lapin::{Channel, Consumer};
+use log::warn;
 use serde_json::Value;
-use tokio::time::timeout;
+use tokio::sync::{oneshot, RwLock};
+use tokio::time::{timeout_at, Instant};
+use tokio_util::sync::CancellationToken;
 
 use crate::api::client::input_consumer::input_consumer::InputConsumer;
 use crate::api::shared::request::Request;
+use crate::api::shared::response::decode_response;
 use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::shared::amqp_connector::AmqpConnector;
 use crate::r#impl::api::shared::amqp_queue_rpc_publisher::AmqpQueueRpcPublisher;
 
+///
+/// Pseudo-queue of RabbitMQ's direct reply-to.
+///
+pub const DIRECT_REPLY_TO_QUEUE: &str = "amq.rabbitmq.reply-to";
+
+type PendingReplies = Arc<Mutex<HashMap<String, oneshot::Sender<Value>>>>;
+
+///
+/// Sends requests to an `AmqpInput` and waits for their replies, which are all consumed by a
+/// single consumer started along the first request and matched to their request by correlation
+/// id, so many requests can be sent concurrently. Replies whose request is not waiting anymore,
+/// e.g. because it timed out, are discarded.
+///
 pub struct AmqpInputConsumer {
-    channel: Arc<Channel>,
+    channel: RwLock<Arc<Channel>>,
+    connector: Option<Arc<AmqpConnector>>,
+    connector_user: usize,
     publisher: AmqpQueueRpcPublisher,
     timeout_after: Duration,
+    reply_consumer: tokio::sync::Mutex<Option<ReplyConsumer>>,
+}
+
+///
+/// Consumer of the replies, bound to the channel it was started on.
+///
+struct ReplyConsumer {
+    route: ReplyRoute,
+}
+
+impl Drop for ReplyConsumer {
+    fn drop(&mut self) {
+        self.route.shutdown_token.cancel();
+    }
+}
+
+///
+/// What a request needs from the reply consumer: the channel to publish through, the queue
+/// its reply is sent to and the requests waiting for a reply of this consumer, which are only
+/// failed by this consumer once it stops.
+///
+#[derive(Clone)]
+struct ReplyRoute {
+    channel: Arc<Channel>,
+    reply_to: ShortString,
+    pending_replies: PendingReplies,
+    shutdown_token: CancellationToken,
 }
 
 impl AmqpInputConsumer {
@@ -26,42 +75,120 @@ impl AmqpInputConsumer {
         timeout_after_milliseconds: u64,
     ) -> AmqpInputConsumer {
         AmqpInputConsumer {
-            channel,
+            channel: RwLock::new(channel),
+            connector: None,
+            connector_user: 0usize,
             publisher,
             timeout_after: Duration::from_millis(timeout_after_milliseconds),
+            reply_consumer: tokio::sync::Mutex::new(None),
         }
     }
-}
 
-#[async_trait]
-impl InputConsumer for AmqpInputConsumer {
-    async fn send_request(&self, request: Request) -> Result<Value, Error> {
-        let request_payload = match serde_json::to_vec::<Request>(&request) {
-            Ok(request_payload) => request_payload,
-            Err(error) => {
-                return Err(Error::new(
-                    ErrorKind::RequestError,
-                    format!("failed to serialize request: {}", error),
-                ));
-            }
+    ///
+    /// Connector the consumer gets a new channel from once its channel is closed, e.g. because
+    /// the broker restarted. The reconnection is bounded by the request's timeout.
+    ///
+    pub fn with_connector(mut self, connector: Arc<AmqpConnector>) -> AmqpInputConsumer {
+        self.connector_user = connector.new_user();
+        self.connector = Some(connector);
+        self
+    }
+
+    async fn try_get_channel(&self, deadline: Instant) -> Result<Arc<Channel>, Error> {
+        let channel = self.channel.read().await.clone();
+
+        let connector = match &self.connector {
+            Some(connector) if !channel.status().connected() => connector,
+            _ => return Ok(channel),
         };
 
-        let queue = match timeout(
-            self.timeout_after,
-            self.channel.queue_declare(
-                self.publisher.response().queue().name(),
-                self.publisher.response().queue().declare().options,
-                self.publisher
-                    .response()
-                    .queue()
-                    .declare()
-                    .arguments
-                    .clone(),
-            ),
-        )
-        .await
-        {
-            Ok(result) => match result {
+        let mut channel = self.channel.write().await;
+
+        // another request may have reconnected while this one waited for the lock
+        if channel.status().connected() {
+            return Ok(channel.clone());
+        }
+
+        connector.report_lost(self.connector_user);
+
+        match timeout_at(deadline, connector.get_channel()).await {
+            Ok(new_channel) => {
+                *channel = new_channel.clone();
+
+                Ok(new_channel)
+            }
+            Err(error) => Err(Error::new(
+                ErrorKind::TimeoutError,
+                format!("timed out reconnecting: {}", error),
+            )),
+        }
+    }
+
+    ///
+    /// Route of the reply consumer running on the current channel, starting one when there is
+    /// none. Direct reply-to requires the requests to be published through the channel
+    /// consuming the replies.
+    ///
+    async fn try_get_reply_route(&self, deadline: Instant) -> Result<ReplyRoute, Error> {
+        let channel = self.try_get_channel(deadline).await?;
+        let mut reply_consumer = self.reply_consumer.lock().await;
+
+        if let Some(running_reply_consumer) = reply_consumer.as_ref() {
+            if Arc::ptr_eq(&running_reply_consumer.route.channel, &channel)
+                && !running_reply_consumer.route.shutdown_token.is_cancelled()
+            {
+                return Ok(running_reply_consumer.route.clone());
+            }
+        }
+
+        let started_reply_consumer =
+            match timeout_at(deadline, self.try_start_reply_consumer(channel.clone())).await {
+                Ok(result) => result?,
+                Err(error) => {
+                    return Err(Error::new(
+                        ErrorKind::TimeoutError,
+                        format!("timed out starting reply consumer: {}", error),
+                    ));
+                }
+            };
+
+        if let Some(connector) = &self.connector {
+            connector.report_up(self.connector_user);
+        }
+
+        let route = started_reply_consumer.route.clone();
+        *reply_consumer = Some(started_reply_consumer);
+
+        Ok(route)
+    }
+
+    async fn try_start_reply_consumer(
+        &self,
+        channel: Arc<Channel>,
+    ) -> Result<ReplyConsumer, Error> {
+        let response = self.publisher.response();
+
+        let (reply_to, consume_options, consume_arguments) = if self.publisher.direct_reply_to() {
+            // direct reply-to replies can only be consumed without acknowledgement
+            let consume_options = BasicConsumeOptions {
+                no_ack: true,
+                ..*response.consume().options()
+            };
+
+            (
+                ShortString::from(DIRECT_REPLY_TO_QUEUE),
+                consume_options,
+                FieldTable::default(),
+            )
+        } else {
+            let queue = match channel
+                .queue_declare(
+                    response.queue().name(),
+                    response.queue().declare().options,
+                    response.queue().declare().arguments.clone(),
+                )
+                .await
+            {
                 Ok(queue) => queue,
                 Err(error) => {
                     return Err(Error::new(
@@ -69,28 +196,112 @@ impl InputConsumer for AmqpInputConsumer {
                         format!("failed to create response queue: {}", error),
                     ));
                 }
-            },
+            };
+
+            if let Err(error) = channel
+                .basic_qos(response.qos().prefetch_count(), *response.qos().options())
+                .await
+            {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failure basic qos: {}", error),
+                ));
+            }
+
+            (
+                queue.name().clone(),
+                *response.consume().options(),
+                response.consume().arguments().clone(),
+            )
+        };
+
+        let consumer = match channel
+            .basic_consume(reply_to.as_str(), "", consume_options, consume_arguments)
+            .await
+        {
+            Ok(consumer) => consumer,
             Err(error) => {
                 return Err(Error::new(
                     ErrorKind::ApiError,
-                    format!("timed out creating response queue: {}", error),
+                    format!("failed to create consumer: {}", error),
                 ));
             }
         };
 
-        let reply_to: ShortString = queue.name().clone();
-        let correlation_id: ShortString = ShortString::from(uuid::Uuid::new_v4().to_string());
+        let route = ReplyRoute {
+            channel,
+            reply_to,
+            pending_replies: Arc::new(Mutex::new(HashMap::new())),
+            shutdown_token: CancellationToken::new(),
+        };
+
+        tokio::spawn(consume_replies(
+            route.channel.clone(),
+            consumer,
+            !consume_options.no_ack,
+            route.pending_replies.clone(),
+            route.shutdown_token.clone(),
+        ));
+
+        Ok(ReplyConsumer { route })
+    }
+}
+
+fn remove_pending_reply(pending_replies: &PendingReplies, correlation_id: &str) {
+    match pending_replies.lock() {
+        Ok(mut pending_replies) => pending_replies.remove(correlation_id),
+        Err(poisoned) => poisoned.into_inner().remove(correlation_id),
+    };
+}
+
+#[async_trait]
+impl InputConsumer for AmqpInputConsumer {
+    async fn send_request(&self, request: Request) -> Result<Value, Error> {
+        let request_payload = match serde_json::to_vec::<Request>(&request) {
+            Ok(request_payload) => request_payload,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::RequestError,
+                    format!("failed to serialize request: {}", error),
+                ));
+            }
+        };
+
+        // reconnecting, publishing and waiting for the reply share the request's timeout
+        let deadline = Instant::now() + self.timeout_after;
+        let route = self.try_get_reply_route(deadline).await?;
+
+        let correlation_id = uuid::Uuid::new_v4().to_string();
         let properties = self
             .publisher
             .publish()
             .properties()
             .clone()
-            .with_reply_to(reply_to)
-            .with_correlation_id(correlation_id);
+            .with_reply_to(route.reply_to.clone())
+            .with_correlation_id(ShortString::from(correlation_id.clone()));
+
+        let (reply_sender, reply_receiver) = oneshot::channel::<Value>();
+
+        match route.pending_replies.lock() {
+            Ok(mut pending_replies) => pending_replies.insert(correlation_id.clone(), reply_sender),
+            Err(poisoned) => poisoned
+                .into_inner()
+                .insert(correlation_id.clone(), reply_sender),
+        };
+
+        // the consumer fails the requests registered before it stops, not the ones after
+        if route.shutdown_token.is_cancelled() {
+            remove_pending_reply(&route.pending_replies, &correlation_id);
 
-        match timeout(
-            self.timeout_after,
-            self.channel.basic_publish(
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                "reply consumer stopped before the request was published",
+            ));
+        }
+
+        match timeout_at(
+            deadline,
+            route.channel.basic_publish(
                 self.publisher.publish().exchange(),
                 self.publisher.queue_name(),
                 *self.publisher.publish().options(),
@@ -102,6 +313,8 @@ impl InputConsumer for AmqpInputConsumer {
         {
             Ok(result) => {
                 if let Err(error) = result {
+                    remove_pending_reply(&route.pending_replies, &correlation_id);
+
                     return Err(Error::new(
                         ErrorKind::ApiError,
                         format!("failed to publish request: {}", error),
@@ -109,77 +322,169 @@ impl InputConsumer for AmqpInputConsumer {
                 }
             }
             Err(error) => {
+                remove_pending_reply(&route.pending_replies, &correlation_id);
+
                 return Err(Error::new(
-                    ErrorKind::ApiError,
+                    ErrorKind::TimeoutError,
                     format!("timed out publishing request: {}", error),
                 ));
             }
         }
 
-        let mut consumer = match timeout(
-            self.timeout_after,
-            self.channel.basic_consume(
-                self.publisher.response().queue().name(),
-                "",
-                *self.publisher.response().consume().options(),
-                self.publisher.response().consume().arguments().clone(),
-            ),
-        )
-        .await
-        {
+        let value = match timeout_at(deadline, reply_receiver).await {
             Ok(result) => match result {
-                Ok(consumer) => consumer,
+                Ok(value) => value,
                 Err(error) => {
                     return Err(Error::new(
                         ErrorKind::ApiError,
-                        format!("failed to create consumer: {}", error),
+                        format!("failed to consume response: {}", error),
                     ));
                 }
             },
             Err(error) => {
+                remove_pending_reply(&route.pending_replies, &correlation_id);
+
                 return Err(Error::new(
-                    ErrorKind::ApiError,
-                    format!("timed out creating consumer: {}", error),
+                    ErrorKind::TimeoutError,
+                    format!("timed out consuming response: {}", error),
                 ));
             }
         };
 
-        let delivery = match timeout(self.timeout_after, consumer.try_next()).await {
-            Ok(result) => match result {
-                Ok(delivery) => match delivery {
-                    Some(delivery) => delivery,
-                    None => {
-                        return Err(Error::new(
-                            ErrorKind::ApiError,
-                            "received an empty delivery",
-                        ));
-                    }
-                },
-                Err(error) => {
-                    return Err(Error::new(
-                        ErrorKind::ApiError,
-                        format!("failed to consume response: {}", error),
-                    ));
+        decode_response(value)
+    }
+}
+
+async fn consume_replies(
+    channel: Arc<Channel>,
+    mut consumer: Consumer,
+    acknowledge: bool,
+    pending_replies: PendingReplies,
+    shutdown_token: CancellationToken,
+) {
+    loop {
+        let delivery = tokio::select! {
+            _ = shutdown_token.cancelled() => {
+                if let Err(error) = channel
+                    .basic_cancel(consumer.tag().as_str(), BasicCancelOptions::default())
+                    .await
+                {
+                    warn!("failed to cancel reply consumer: {}", error);
                 }
-            },
-            Err(error) => {
-                return Err(Error::new(
-                    ErrorKind::ApiError,
-                    format!("timed out consuming response: {}", error),
-                ));
+
+                break;
             }
+            delivery = consumer.next() => match delivery {
+                Some(Ok(delivery)) => delivery,
+                Some(Err(error)) => {
+                    warn!("reply consumer got an error: {}", error);
+                    break;
+                }
+                None => break,
+            },
         };
 
-        let value = match serde_json::from_slice::<Value>(delivery.data.as_slice()) {
-            Ok(value) => value,
-            Err(error) => {
-                return Err(Error::new(
-                    ErrorKind::ApiError,
-                    format!("failed to deserialize delivery: {}", error),
-                ));
+        if acknowledge {
+            if let Err(error) = delivery.ack(BasicAckOptions::default()).await {
+                warn!("failed to acknowledge reply: {}", error);
             }
-        };
+        }
 
-        Ok(value)
+        route_reply(
+            &pending_replies,
+            delivery.properties.correlation_id().as_ref(),
+            delivery.data.as_slice(),
+        );
     }
+
+    // the next request starts a new reply consumer with its own pending replies, whereas the
+    // requests still waiting for a reply of this one fail right away
+    shutdown_token.cancel();
+
+    match pending_replies.lock() {
+        Ok(mut pending_replies) => pending_replies.clear(),
+        Err(poisoned) => poisoned.into_inner().clear(),
+    }
+}
+
+///
+/// Hands the reply to the request of its correlation id, discarding it when that request is not
+/// waiting for it anymore.
+///
+fn route_reply(
+    pending_replies: &PendingReplies,
+    correlation_id: Option<&ShortString>,
+    data: &[u8],
+) {
+    let correlation_id = match correlation_id {
+        Some(correlation_id) => correlation_id.as_str(),
+        None => {
+            warn!("discarding reply without correlation id");
+            return;
+        }
+    };
+
+    let reply_sender = match pending_replies.lock() {
+        Ok(mut pending_replies) => pending_replies.remove(correlation_id),
+        Err(poisoned) => poisoned.into_inner().remove(correlation_id),
+    };
+
+    let reply_sender = match reply_sender {
+        Some(reply_sender) => reply_sender,
+        None => {
+            warn!(
+                "discarding reply with unknown correlation id '{}'",
+                correlation_id
+            );
+            return;
+        }
+    };
+
+    match serde_json::from_slice::<Value>(data) {
+        Ok(value) => {
+            let _ = reply_sender.send(value);
+        }
+        // dropping the sender fails the request waiting for the reply
+        Err(error) => warn!("failed to deserialize delivery: {}", error),
+    }
+}
+
+#[tokio::test]
+pub async fn route_replies_by_correlation_id() {
+    let pending_replies: PendingReplies = Arc::new(Mutex::new(HashMap::new()));
+    let (first_sender, first_receiver) = oneshot::channel::<Value>();
+    let (second_sender, mut second_receiver) = oneshot::channel::<Value>();
+
+    if let Ok(mut pending_replies) = pending_replies.lock() {
+        pending_replies.insert("first".to_string(), first_sender);
+        pending_replies.insert("second".to_string(), second_sender);
+    }
+
+    route_reply(
+        &pending_replies,
+        Some(&ShortString::from("first")),
+        br#"{"Ok": "first reply"}"#,
+    );
+    // replies of requests which timed out and replies without correlation id are discarded
+    route_reply(
+        &pending_replies,
+        Some(&ShortString::from("orphan")),
+        br#"{"Ok": "orphan reply"}"#,
+    );
+    route_reply(&pending_replies, None, br#"{"Ok": "anonymous reply"}"#);
+
+    assert_eq!(
+        serde_json::json!({"Ok": "first reply"}),
+        first_receiver.await.expect("first request got no reply")
+    );
+    assert!(second_receiver.try_recv().is_err());
+    assert_eq!(
+        vec!["second".to_string()],
+        pending_replies
+            .lock()
+            .expect("pending replies are poisoned")
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>()
+    );
 }
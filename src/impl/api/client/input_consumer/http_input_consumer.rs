@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::api::client::input_consumer::input_consumer::InputConsumer;
+use crate::api::shared::request::Request;
+use crate::api::shared::response::decode_response;
+use crate::core::error::{Error, ErrorKind};
+
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5u64);
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90u64);
+pub const DEFAULT_POOL_MAX_IDLE_PER_HOST: usize = 32usize;
+
+///
+/// Configuration of an `HttpInputConsumer`.
+///
+#[derive(Clone)]
+pub struct HttpInputConsumerConfig {
+    url: String,
+    timeout_after: Duration,
+    connect_timeout: Duration,
+    pool_idle_timeout: Duration,
+    pool_max_idle_per_host: usize,
+}
+
+impl HttpInputConsumerConfig {
+    ///
+    /// Requests are sent to the given URL of an `HttpInput`, e.g. `http://cp-organization:8080`,
+    /// and fail once the given timeout is over without a reply.
+    ///
+    pub fn new(url: String, timeout_after_milliseconds: u64) -> HttpInputConsumerConfig {
+        HttpInputConsumerConfig {
+            url,
+            timeout_after: Duration::from_millis(timeout_after_milliseconds),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            pool_idle_timeout: DEFAULT_POOL_IDLE_TIMEOUT,
+            pool_max_idle_per_host: DEFAULT_POOL_MAX_IDLE_PER_HOST,
+        }
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> HttpInputConsumerConfig {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    ///
+    /// Time after which the idle pooled connections are closed.
+    ///
+    pub fn with_pool_idle_timeout(
+        mut self,
+        pool_idle_timeout: Duration,
+    ) -> HttpInputConsumerConfig {
+        self.pool_idle_timeout = pool_idle_timeout;
+        self
+    }
+
+    ///
+    /// Maximum amount of idle connections kept open towards the input.
+    ///
+    pub fn with_pool_max_idle_per_host(
+        mut self,
+        pool_max_idle_per_host: usize,
+    ) -> HttpInputConsumerConfig {
+        self.pool_max_idle_per_host = pool_max_idle_per_host;
+        self
+    }
+}
+
+///
+/// Sends requests to an `HttpInput` through `POST /`, reusing the connections among requests.
+/// The request token is forwarded as the bearer token of the `Authorization` header too.
+///
+pub struct HttpInputConsumer {
+    client: Client,
+    url: String,
+}
+
+impl HttpInputConsumer {
+    pub fn try_new(config: HttpInputConsumerConfig) -> Result<HttpInputConsumer, Error> {
+        let client = match Client::builder()
+            .timeout(config.timeout_after)
+            .connect_timeout(config.connect_timeout)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .build()
+        {
+            Ok(client) => client,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!("failed to create http client: {}", error),
+                ))
+            }
+        };
+
+        let url = format!("{}/", config.url.trim_end_matches('/'));
+
+        Ok(HttpInputConsumer { client, url })
+    }
+}
+
+#[async_trait]
+impl InputConsumer for HttpInputConsumer {
+    async fn send_request(&self, request: Request) -> Result<Value, Error> {
+        let mut http_request = self.client.post(self.url.as_str()).json(&request);
+
+        if !request.header().token().is_empty() {
+            http_request = http_request.bearer_auth(request.header().token());
+        }
+
+        let http_response = match http_request.send().await {
+            Ok(http_response) => http_response,
+            Err(error) => {
+                if error.is_timeout() {
+                    return Err(Error::new(
+                        ErrorKind::TimeoutError,
+                        format!("timed out sending request: {}", error),
+                    ));
+                }
+
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to send request: {}", error),
+                ));
+            }
+        };
+
+        let status = http_response.status();
+
+        let value = match http_response.json::<Value>().await {
+            Ok(value) => value,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!(
+                        "failed to deserialize response with status '{}': {}",
+                        status, error
+                    ),
+                ));
+            }
+        };
+
+        decode_response(value)
+    }
+}
+
+#[tokio::test]
+pub async fn send_requests_to_http_input() {
+    use crate::api::shared::request_header::RequestHeader;
+    use crate::r#impl::api::server::input::http_input::{start_echo_dispatch, HttpInputConfig};
+    use serde_json::json;
+
+    let (local_address, cancellation_token, handles) = start_echo_dispatch(HttpInputConfig::new(
+        "127.0.0.1:0".parse().expect("invalid address"),
+    ))
+    .await;
+
+    let input_consumer = HttpInputConsumer::try_new(HttpInputConsumerConfig::new(
+        format!("http://{}", local_address),
+        1000u64,
+    ))
+    .expect("failed to create http input consumer");
+
+    let response = input_consumer
+        .send_request(Request::new(
+            RequestHeader::new("echo".to_string(), "token".to_string()),
+            json!("payload"),
+        ))
+        .await;
+
+    assert_eq!(
+        Ok(json!({ "token": "token", "tenant": null, "payload": "payload" })),
+        response
+    );
+
+    let error = input_consumer
+        .send_request(Request::new(
+            RequestHeader::new("unknown".to_string(), "token".to_string()),
+            Value::Null,
+        ))
+        .await
+        .expect_err("expected an error response");
+
+    assert_eq!(ErrorKind::UnknownActionError, error.kind());
+
+    cancellation_token.cancel();
+    futures_util::future::join_all(handles).await;
+}
+
+#[tokio::test]
+pub async fn time_out_requests_without_reply() {
+    use crate::api::shared::request_header::RequestHeader;
+
+    // accepts the connections without ever replying
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("failed to bind listener");
+    let local_address = listener.local_addr().expect("listener has no address");
+    let handle = tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((connection, _)) = listener.accept().await {
+            connections.push(connection);
+        }
+    });
+
+    let input_consumer = HttpInputConsumer::try_new(HttpInputConsumerConfig::new(
+        format!("http://{}", local_address),
+        100u64,
+    ))
+    .expect("failed to create http input consumer");
+
+    let error = input_consumer
+        .send_request(Request::new(
+            RequestHeader::new("echo".to_string(), "token".to_string()),
+            Value::Null,
+        ))
+        .await
+        .expect_err("expected the request to time out");
+
+    assert_eq!(ErrorKind::TimeoutError, error.kind());
+
+    handle.abort();
+}
@@ -0,0 +1,341 @@
+use std::time::Duration;
+
+use async_channel::Sender;
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::time::timeout;
+
+use crate::api::client::input_consumer::input_consumer::InputConsumer;
+use crate::api::shared::request::Request;
+use crate::api::shared::response::decode_response;
+use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::server::input::loopback_input::{LoopbackDelivery, LoopbackReply};
+
+///
+/// Sends requests to a `LoopbackInput`. Each request gets its own reply channel and correlation
+/// id, which its reply must carry, and the reply is decoded like the `AmqpInputConsumer` does.
+///
+#[derive(Clone)]
+pub struct LoopbackInputConsumer {
+    sender: Sender<LoopbackDelivery>,
+    timeout_after: Duration,
+}
+
+impl LoopbackInputConsumer {
+    pub(crate) fn new(
+        sender: Sender<LoopbackDelivery>,
+        timeout_after_milliseconds: u64,
+    ) -> LoopbackInputConsumer {
+        LoopbackInputConsumer {
+            sender,
+            timeout_after: Duration::from_millis(timeout_after_milliseconds),
+        }
+    }
+
+    ///
+    /// Sends a request without reply channel, so the input does not reply to it, like an AMQP
+    /// request without reply-to.
+    ///
+    pub async fn send_request_without_reply(&self, request: Request) -> Result<(), Error> {
+        let delivery = LoopbackDelivery {
+            request,
+            reply_to: None,
+            correlation_id: uuid::Uuid::new_v4().to_string(),
+        };
+
+        self.publish(delivery).await
+    }
+
+    async fn publish(&self, delivery: LoopbackDelivery) -> Result<(), Error> {
+        match timeout(self.timeout_after, self.sender.send(delivery)).await {
+            Ok(result) => match result {
+                Ok(_) => Ok(()),
+                Err(error) => Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to publish request: {}", error),
+                )),
+            },
+            Err(error) => Err(Error::new(
+                ErrorKind::TimeoutError,
+                format!("timed out publishing request: {}", error),
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl InputConsumer for LoopbackInputConsumer {
+    async fn send_request(&self, request: Request) -> Result<Value, Error> {
+        let (reply_to, reply_receiver) = async_channel::bounded::<LoopbackReply>(1usize);
+        let correlation_id = uuid::Uuid::new_v4().to_string();
+
+        let delivery = LoopbackDelivery {
+            request,
+            reply_to: Some(reply_to),
+            correlation_id: correlation_id.clone(),
+        };
+
+        self.publish(delivery).await?;
+
+        let reply = match timeout(self.timeout_after, reply_receiver.recv()).await {
+            Ok(result) => match result {
+                Ok(reply) => reply,
+                Err(error) => {
+                    return Err(Error::new(
+                        ErrorKind::ApiError,
+                        format!("failed to consume response: {}", error),
+                    ));
+                }
+            },
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::TimeoutError,
+                    format!("timed out consuming response: {}", error),
+                ));
+            }
+        };
+
+        if reply.correlation_id != correlation_id {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!(
+                    "received a response with correlation id '{}' instead of '{}'",
+                    reply.correlation_id, correlation_id
+                ),
+            ));
+        }
+
+        decode_response(reply.value)
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+pub enum LoopbackLogicRequest {
+    CreateOrg(String, tokio::sync::oneshot::Sender<Result<String, Error>>),
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+pub enum LoopbackStorageRequest {
+    CreateOrg(String, tokio::sync::oneshot::Sender<Result<String, Error>>),
+}
+
+#[tokio::test]
+pub async fn exercise_whole_microservice_in_process() {
+    use std::collections::HashMap;
+    use std::mem;
+    use std::sync::Arc;
+
+    use serde_json::json;
+    use tokio_util::sync::CancellationToken;
+
+    use crate::api::server::input::action::Action;
+    use crate::api::shared::request_header::RequestHeader;
+    use crate::logic::executor::{timeout_receive_storage_response, timeout_send_storage_request};
+    use crate::r#impl::api::server::input::loopback_input::LoopbackInput;
+
+    let create_org_action: Action<LoopbackLogicRequest> = Action::new(
+        "create_org".to_string(),
+        Arc::new(move |request, logic_request_sender| {
+            Box::pin(async move {
+                let name = match request.payload().as_str() {
+                    Some(name) => name.to_string(),
+                    None => return Err(Error::new(ErrorKind::RequestError, "expected a name")),
+                };
+
+                let (replier, receiver) = tokio::sync::oneshot::channel::<Result<String, Error>>();
+                logic_request_sender
+                    .send(LoopbackLogicRequest::CreateOrg(name, replier))
+                    .await?;
+
+                match receiver.await {
+                    Ok(result) => result.map(Value::String),
+                    Err(error) => Err(Error::new(ErrorKind::ApiError, error.to_string())),
+                }
+            })
+        }),
+        Vec::new(),
+    );
+
+    let logic_executor: crate::logic::executor::Executor<
+        LoopbackLogicRequest,
+        LoopbackStorageRequest,
+    > = Arc::new(move |logic_request, storage_request_sender| {
+        Box::pin(async move {
+            let LoopbackLogicRequest::CreateOrg(name, api_replier) = logic_request;
+
+            let (storage_replier, storage_receiver) =
+                tokio::sync::oneshot::channel::<Result<String, Error>>();
+            let api_replier = timeout_send_storage_request(
+                100u64,
+                LoopbackStorageRequest::CreateOrg(name, storage_replier),
+                &storage_request_sender,
+                api_replier,
+            )
+            .await?;
+            let (api_replier, id) =
+                timeout_receive_storage_response(100u64, storage_receiver, api_replier).await?;
+
+            if api_replier.send(Ok(id)).is_err() {
+                return Err(Error::new(ErrorKind::LogicError, "failed to reply to api"));
+            }
+
+            Ok(())
+        })
+    });
+
+    let storage_executor: crate::storage::executor::Executor<LoopbackStorageRequest> =
+        Arc::new(move |storage_request| {
+            Box::pin(async move {
+                let LoopbackStorageRequest::CreateOrg(name, replier) = storage_request;
+
+                if replier.send(Ok(format!("org-{}", name))).is_err() {
+                    return Err(Error::new(ErrorKind::StorageError, "failed to reply"));
+                }
+
+                Ok(())
+            })
+        });
+
+    let cancellation_token = CancellationToken::new();
+    let (logic_request_sender, logic_request_receiver) =
+        async_channel::unbounded::<LoopbackLogicRequest>();
+    let (storage_request_sender, storage_request_receiver) =
+        async_channel::unbounded::<LoopbackStorageRequest>();
+
+    let input = LoopbackInput::new(8usize);
+    let input_consumer = input.consumer(500u64);
+
+    let api_dispatch: crate::api::server::dispatch::Dispatch<LoopbackInput, LoopbackLogicRequest> =
+        crate::api::server::dispatch::Dispatch::new(
+            vec![input],
+            HashMap::from([("create_org".to_string(), create_org_action)]),
+            logic_request_sender,
+            Vec::new(),
+        );
+    let logic_dispatch = crate::logic::dispatch::Dispatch::new(
+        logic_request_receiver,
+        HashMap::from([(
+            mem::discriminant(&LoopbackLogicRequest::CreateOrg(
+                String::new(),
+                tokio::sync::oneshot::channel().0,
+            )),
+            logic_executor,
+        )]),
+        storage_request_sender,
+        cancellation_token.clone(),
+    );
+    let storage_dispatch = crate::storage::dispatch::Dispatch::new(
+        storage_request_receiver,
+        HashMap::from([(
+            mem::discriminant(&LoopbackStorageRequest::CreateOrg(
+                String::new(),
+                tokio::sync::oneshot::channel().0,
+            )),
+            storage_executor,
+        )]),
+        cancellation_token.clone(),
+    );
+
+    let handles = api_dispatch.run(cancellation_token.clone()).await;
+    tokio::spawn(logic_dispatch.run());
+    tokio::spawn(storage_dispatch.run());
+
+    let response = input_consumer
+        .send_request(Request::new(
+            RequestHeader::new("create_org".to_string(), "token".to_string()),
+            json!("cuplan"),
+        ))
+        .await;
+
+    assert_eq!(Ok(json!("org-cuplan")), response);
+
+    let error = input_consumer
+        .send_request(Request::new(
+            RequestHeader::new("create_org".to_string(), "token".to_string()),
+            Value::Null,
+        ))
+        .await
+        .expect_err("expected an error response");
+
+    assert_eq!(ErrorKind::RequestError, error.kind());
+
+    cancellation_token.cancel();
+    futures_util::future::join_all(handles).await;
+}
+
+#[tokio::test]
+pub async fn fail_with_timeout_error_when_no_reply_arrives() {
+    use crate::api::shared::request_header::RequestHeader;
+    use crate::r#impl::api::server::input::loopback_input::LoopbackInput;
+
+    let input = LoopbackInput::new(1usize);
+    let input_consumer = input.consumer(50u64);
+
+    let error = input_consumer
+        .send_request(Request::new(
+            RequestHeader::new("create_org".to_string(), "token".to_string()),
+            Value::Null,
+        ))
+        .await
+        .expect_err("expected request to time out");
+
+    assert_eq!(ErrorKind::TimeoutError, error.kind());
+}
+
+#[tokio::test]
+pub async fn reject_replies_of_other_requests() {
+    use crate::api::shared::request_header::RequestHeader;
+
+    let (sender, receiver) = async_channel::bounded::<LoopbackDelivery>(1usize);
+    let input_consumer = LoopbackInputConsumer::new(sender, 1_000u64);
+
+    tokio::spawn(async move {
+        let delivery = receiver.recv().await.expect("expected a delivery");
+        let reply = LoopbackReply {
+            correlation_id: "other".to_string(),
+            value: Value::Null,
+        };
+
+        delivery
+            .reply_to
+            .expect("expected a reply channel")
+            .send(reply)
+            .await
+            .expect("failed to reply");
+    });
+
+    let error = input_consumer
+        .send_request(Request::new(
+            RequestHeader::new("create_org".to_string(), "token".to_string()),
+            Value::Null,
+        ))
+        .await
+        .expect_err("expected reply of another request to be rejected");
+
+    assert_eq!(ErrorKind::ApiError, error.kind());
+}
+
+#[tokio::test]
+pub async fn send_requests_without_reply() {
+    use crate::api::server::input::input::Input;
+    use crate::api::shared::request_header::RequestHeader;
+    use crate::r#impl::api::server::input::loopback_input::LoopbackInput;
+
+    let mut input = LoopbackInput::new(1usize);
+    let input_consumer = input.consumer(1_000u64);
+
+    input_consumer
+        .send_request_without_reply(Request::new(
+            RequestHeader::new("create_org".to_string(), "token".to_string()),
+            Value::Null,
+        ))
+        .await
+        .expect("failed to send request");
+
+    let input_data = input.receive().await.expect("failed to receive request");
+
+    assert_eq!("create_org", input_data.request.header().action());
+    assert!((input_data.replier)(Value::Null).await.is_ok());
+}
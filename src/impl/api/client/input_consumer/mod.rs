@@ -1 +1,4 @@
 pub mod amqp_input_consumer;
+pub mod http_input_consumer;
+pub mod loopback_input_consumer;
+pub mod unix_socket_input_consumer;
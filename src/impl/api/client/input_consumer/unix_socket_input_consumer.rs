@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use log::warn;
+use serde_json::Value;
+use tokio::net::UnixStream;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use crate::api::client::input_consumer::input_consumer::InputConsumer;
+use crate::api::shared::request::Request;
+use crate::api::shared::response::decode_response;
+use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::shared::unix_socket_frame::{
+    decode_frame, encode_frame, UnixSocketReplyFrame, UnixSocketRequestFrame,
+};
+
+type PendingReplies = Arc<Mutex<HashMap<String, oneshot::Sender<Value>>>>;
+
+///
+/// Sends requests to a `UnixSocketInput` through a single connection, on which the requests are
+/// multiplexed and their replies matched by correlation id. The replies are decoded like the
+/// `AmqpInputConsumer` does.
+///
+pub struct UnixSocketInputConsumer {
+    frame_sender: UnboundedSender<Bytes>,
+    pending_replies: PendingReplies,
+    timeout_after: Duration,
+}
+
+impl UnixSocketInputConsumer {
+    pub async fn try_new(
+        path: &Path,
+        timeout_after_milliseconds: u64,
+    ) -> Result<UnixSocketInputConsumer, Error> {
+        let stream = match UnixStream::connect(path).await {
+            Ok(stream) => stream,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!(
+                        "failed to connect to unix socket '{}': {}",
+                        path.display(),
+                        error
+                    ),
+                ))
+            }
+        };
+
+        let (mut sink, mut stream) = Framed::new(stream, LengthDelimitedCodec::new()).split();
+        let (frame_sender, mut frame_receiver) = unbounded_channel::<Bytes>();
+        let pending_replies: PendingReplies = Arc::new(Mutex::new(HashMap::new()));
+
+        tokio::spawn(async move {
+            while let Some(frame) = frame_receiver.recv().await {
+                if let Err(error) = sink.send(frame).await {
+                    warn!("failed to send unix socket request: {}", error);
+                    break;
+                }
+            }
+        });
+
+        let reader_pending_replies = pending_replies.clone();
+        tokio::spawn(async move {
+            while let Some(frame) = stream.next().await {
+                let frame = match frame {
+                    Ok(frame) => frame,
+                    Err(error) => {
+                        warn!("failed to read unix socket reply: {}", error);
+                        break;
+                    }
+                };
+
+                let reply_frame = match decode_frame::<UnixSocketReplyFrame>(&frame) {
+                    Ok(reply_frame) => reply_frame,
+                    Err(error) => {
+                        warn!("dropping unix socket reply: {}", error);
+                        continue;
+                    }
+                };
+
+                let reply_sender = match reader_pending_replies.lock() {
+                    Ok(mut pending_replies) => pending_replies.remove(&reply_frame.correlation_id),
+                    Err(poisoned) => poisoned.into_inner().remove(&reply_frame.correlation_id),
+                };
+
+                match reply_sender {
+                    Some(reply_sender) => {
+                        let _ = reply_sender.send(reply_frame.response);
+                    }
+                    None => warn!(
+                        "dropping unix socket reply with unknown correlation id '{}'",
+                        reply_frame.correlation_id
+                    ),
+                }
+            }
+
+            // dropping the senders fails the requests still waiting for their reply
+            match reader_pending_replies.lock() {
+                Ok(mut pending_replies) => pending_replies.clear(),
+                Err(poisoned) => poisoned.into_inner().clear(),
+            }
+        });
+
+        Ok(UnixSocketInputConsumer {
+            frame_sender,
+            pending_replies,
+            timeout_after: Duration::from_millis(timeout_after_milliseconds),
+        })
+    }
+
+    fn remove_pending_reply(&self, correlation_id: &str) {
+        match self.pending_replies.lock() {
+            Ok(mut pending_replies) => pending_replies.remove(correlation_id),
+            Err(poisoned) => poisoned.into_inner().remove(correlation_id),
+        };
+    }
+}
+
+#[async_trait]
+impl InputConsumer for UnixSocketInputConsumer {
+    async fn send_request(&self, request: Request) -> Result<Value, Error> {
+        let correlation_id = uuid::Uuid::new_v4().to_string();
+        let frame = encode_frame(&UnixSocketRequestFrame {
+            correlation_id: correlation_id.clone(),
+            request,
+        })?;
+
+        let (reply_sender, reply_receiver) = oneshot::channel::<Value>();
+
+        match self.pending_replies.lock() {
+            Ok(mut pending_replies) => pending_replies.insert(correlation_id.clone(), reply_sender),
+            Err(poisoned) => poisoned
+                .into_inner()
+                .insert(correlation_id.clone(), reply_sender),
+        };
+
+        if let Err(error) = self.frame_sender.send(frame) {
+            self.remove_pending_reply(&correlation_id);
+
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to publish request: {}", error),
+            ));
+        }
+
+        let value = match timeout(self.timeout_after, reply_receiver).await {
+            Ok(result) => match result {
+                Ok(value) => value,
+                Err(error) => {
+                    return Err(Error::new(
+                        ErrorKind::ApiError,
+                        format!("failed to consume response: {}", error),
+                    ));
+                }
+            },
+            Err(error) => {
+                self.remove_pending_reply(&correlation_id);
+
+                return Err(Error::new(
+                    ErrorKind::TimeoutError,
+                    format!("timed out consuming response: {}", error),
+                ));
+            }
+        };
+
+        decode_response(value)
+    }
+}
+
+#[tokio::test]
+pub async fn multiplex_requests_on_one_connection() {
+    use crate::api::server::dispatch::Dispatch;
+    use crate::api::server::input::action::Action;
+    use crate::api::shared::request_header::RequestHeader;
+    use crate::r#impl::api::server::input::unix_socket_input::{
+        unix_socket_test_path, UnixSocketInput, UnixSocketInputConfig,
+    };
+    use tokio_util::sync::CancellationToken;
+
+    let path = unix_socket_test_path();
+    let input = UnixSocketInput::try_new(UnixSocketInputConfig::new(path.clone()))
+        .expect("failed to start unix socket input");
+
+    let echo_action: Action<()> = Action::new(
+        "echo".to_string(),
+        Arc::new(move |request, _sender| {
+            Box::pin(async move {
+                let delay = request.payload().as_u64().unwrap_or_default();
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+
+                Ok(request.payload().clone())
+            })
+        }),
+        Vec::new(),
+    );
+
+    let (logic_request_sender, _) = async_channel::unbounded::<()>();
+    let dispatch: Dispatch<UnixSocketInput, ()> = Dispatch::new(
+        vec![input],
+        HashMap::from([("echo".to_string(), echo_action)]),
+        logic_request_sender,
+        Vec::new(),
+    );
+
+    let cancellation_token = CancellationToken::new();
+    let handles = dispatch.run(cancellation_token.clone()).await;
+
+    let input_consumer = UnixSocketInputConsumer::try_new(path.as_path(), 1000u64)
+        .await
+        .expect("failed to connect unix socket input consumer");
+
+    let echo_request = |delay: u64| {
+        Request::new(
+            RequestHeader::new("echo".to_string(), "token".to_string()),
+            Value::from(delay),
+        )
+    };
+
+    let (slow_response, fast_response, unknown_response) = tokio::join!(
+        input_consumer.send_request(echo_request(200u64)),
+        input_consumer.send_request(echo_request(0u64)),
+        input_consumer.send_request(Request::new(
+            RequestHeader::new("unknown".to_string(), "token".to_string()),
+            Value::Null,
+        )),
+    );
+
+    assert_eq!(Ok(Value::from(200u64)), slow_response);
+    assert_eq!(Ok(Value::from(0u64)), fast_response);
+    assert_eq!(
+        Some(ErrorKind::UnknownActionError),
+        unknown_response.err().map(|error| error.kind())
+    );
+
+    cancellation_token.cancel();
+    futures_util::future::join_all(handles).await;
+}
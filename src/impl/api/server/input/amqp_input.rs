@@ -2,30 +2,215 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use futures_util::TryStreamExt;
+use lapin::acker::Acker;
 use lapin::message::Delivery;
-use lapin::options::{BasicAckOptions, BasicPublishOptions, BasicRejectOptions};
-use lapin::types::ShortString;
+use lapin::options::{
+    BasicAckOptions, BasicCancelOptions, BasicNackOptions, BasicPublishOptions, BasicRejectOptions,
+};
+use lapin::protocol::constants::REPLY_SUCCESS;
+use lapin::publisher_confirm::Confirmation;
+use lapin::types::{AMQPValue, FieldTable, ShortString};
 use lapin::{BasicProperties, Channel, Consumer};
+use log::{info, warn};
+use serde_json::Value;
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
 use crate::api::server::input::input::Input;
 use crate::api::server::input::input_data::InputData;
 use crate::api::server::input::replier::Replier;
 use crate::api::shared::request::Request;
+use crate::api::shared::response::decode_response;
 use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::shared::amqp_acknowledge_mode::AmqpAcknowledgeMode;
+use crate::r#impl::api::shared::amqp_api_entry::AmqpApiEntry;
+use crate::r#impl::api::shared::amqp_connector::AmqpConnector;
+use crate::r#impl::api::shared::amqp_dead_letter::{
+    try_declare_dead_letter, try_publish_dead_letter, AmqpDeadLetter,
+};
+use crate::r#impl::api::shared::amqp_exchange::try_declare_exchange;
+use crate::r#impl::api::shared::amqp_failure_policy::AmqpFailurePolicy;
+use crate::r#impl::api::shared::amqp_queue_binding::AmqpQueueBinding;
 use crate::r#impl::api::shared::amqp_queue_consumer::AmqpQueueConsumer;
 
+const DELIVERY_COUNT_HEADER: &str = "x-delivery-count";
+
+///
+/// Header of the requests republished to be handled again, counting the deliveries handled
+/// before they were republished.
+///
+pub(crate) const DELIVERY_ATTEMPTS_HEADER: &str = "x-cp-delivery-attempts";
+
 pub struct AmqpInput {
     channel: Arc<Channel>,
     consumer: Consumer,
-    reject_options: BasicRejectOptions,
+    acknowledge_mode: AmqpAcknowledgeMode,
+    settle_options: Arc<SettleOptions>,
+    recovery: Option<Recovery>,
+    ///
+    /// Delivery being read, which is kept when `receive` is cancelled so the next call returns
+    /// it instead of losing a delivery which may already be dead-lettered.
+    ///
+    reading: Option<JoinHandle<Result<ReadDelivery, Error>>>,
+}
+
+///
+/// Request read from a delivery, along the acker of the deliveries acknowledged on receive,
+/// which is only used once the request is handed to the dispatch.
+///
+type ReadDelivery = (InputData, Option<Acker>);
+
+///
+/// What is needed to consume the queue again once the connection is lost.
+///
+#[derive(Clone)]
+struct Recovery {
+    connector: Arc<AmqpConnector>,
+    connector_user: usize,
+    api_entry: AmqpApiEntry,
+}
+
+///
+/// Configuration used to settle the deliveries, shared by the repliers of the deliveries which
+/// are acknowledged after their reply.
+///
+struct SettleOptions {
+    queue_name: String,
+    dead_letter: Option<AmqpDeadLetter>,
     ack_options: BasicAckOptions,
+    reject_options: BasicRejectOptions,
+    failure_policy: AmqpFailurePolicy,
+    max_deliveries: Option<u32>,
 }
 
 impl AmqpInput {
     pub async fn try_new(
         channel: Arc<Channel>,
         queue_consumer: AmqpQueueConsumer,
+    ) -> Result<AmqpInput, Error> {
+        AmqpInput::try_build(channel, queue_consumer, &[], None).await
+    }
+
+    ///
+    /// Declares the exchanges of the entry, its dead-letter exchange and queue, then its queue and
+    /// the queue's bindings, before consuming the queue.
+    ///
+    pub async fn try_from_api_entry(
+        channel: Arc<Channel>,
+        api_entry: AmqpApiEntry,
+    ) -> Result<AmqpInput, Error> {
+        for exchange in &api_entry.exchanges {
+            try_declare_exchange(&channel, exchange).await?;
+        }
+
+        if let Some(dead_letter) = &api_entry.dead_letter {
+            try_declare_dead_letter(&channel, dead_letter).await?;
+        }
+
+        AmqpInput::try_build(
+            channel,
+            api_entry.amqp_queue_consumer,
+            api_entry.bindings.as_slice(),
+            api_entry.dead_letter,
+        )
+        .await
+    }
+
+    ///
+    /// Builds the input like `try_from_api_entry` with a channel of the connector. Once the
+    /// consumer is lost, e.g. because the broker restarted, the input gets a new channel from
+    /// the connector, retrying with backoff, and declares the entry again before resuming.
+    ///
+    pub async fn try_from_connector(
+        connector: Arc<AmqpConnector>,
+        api_entry: AmqpApiEntry,
+    ) -> Result<AmqpInput, Error> {
+        let channel = connector.try_get_channel().await?;
+        let mut input = AmqpInput::try_from_api_entry(channel, api_entry.clone()).await?;
+
+        let connector_user = connector.new_user();
+        connector.report_up(connector_user);
+
+        input.recovery = Some(Recovery {
+            connector,
+            connector_user,
+            api_entry,
+        });
+
+        Ok(input)
+    }
+
+    async fn recover(&mut self, recovery: Recovery) {
+        recovery.connector.report_lost(recovery.connector_user);
+
+        let mut backoff = recovery.connector.backoff();
+
+        loop {
+            let channel = recovery.connector.get_channel().await;
+
+            match AmqpInput::try_from_api_entry(channel, recovery.api_entry.clone()).await {
+                Ok(input) => {
+                    recovery.connector.report_up(recovery.connector_user);
+
+                    info!(
+                        "amqp input consumes '{}' again",
+                        input.settle_options.queue_name
+                    );
+
+                    *self = AmqpInput {
+                        recovery: Some(recovery),
+                        ..input
+                    };
+
+                    return;
+                }
+                Err(error) => {
+                    recovery.connector.report_lost(recovery.connector_user);
+
+                    let delay = backoff.next_delay();
+                    warn!(
+                        "failed to recover amqp input, retrying in {} ms: {}",
+                        delay.as_millis(),
+                        error
+                    );
+
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    async fn next_delivery(&mut self) -> Result<Delivery, Error> {
+        loop {
+            let error = match self.consumer.try_next().await {
+                Ok(optional_delivery) => match optional_delivery {
+                    Some(delivery) => return Ok(delivery),
+                    None => Error::new(ErrorKind::ApiError, "consumer got an empty delivery"),
+                },
+                Err(error) => Error::new(
+                    ErrorKind::ApiError,
+                    format!("consumer got an error: {}", error),
+                ),
+            };
+
+            // without a connector the consumer cannot be recovered
+            let recovery = match &self.recovery {
+                Some(recovery) => recovery.clone(),
+                None => return Err(error),
+            };
+
+            // the input is only replaced once recovered, so a cancelled recovery is started
+            // again by the next receive
+            warn!("amqp input lost its consumer, recovering: {}", error);
+            self.recover(recovery).await;
+        }
+    }
+
+    async fn try_build(
+        channel: Arc<Channel>,
+        queue_consumer: AmqpQueueConsumer,
+        bindings: &[AmqpQueueBinding],
+        dead_letter: Option<AmqpDeadLetter>,
     ) -> Result<AmqpInput, Error> {
         let _queue = match channel
             .queue_declare(
@@ -44,6 +229,29 @@ impl AmqpInput {
             }
         };
 
+        for binding in bindings {
+            if let Err(error) = channel
+                .queue_bind(
+                    queue_consumer.queue().name(),
+                    binding.exchange(),
+                    binding.routing_key(),
+                    *binding.options(),
+                    binding.arguments().clone(),
+                )
+                .await
+            {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!(
+                        "failed to bind queue to exchange '{}' with routing key '{}': {}",
+                        binding.exchange(),
+                        binding.routing_key(),
+                        error
+                    ),
+                ));
+            }
+        }
+
         match channel
             .basic_qos(
                 queue_consumer.qos().prefetch_count(),
@@ -70,14 +278,22 @@ impl AmqpInput {
             }
         };
 
-        let reject_options = *queue_consumer.reject();
-        let ack_options = *queue_consumer.acknowledge();
+        let settle_options = SettleOptions {
+            queue_name: queue_consumer.queue().name().to_string(),
+            dead_letter,
+            ack_options: *queue_consumer.acknowledge(),
+            reject_options: *queue_consumer.reject(),
+            failure_policy: queue_consumer.failure_policy(),
+            max_deliveries: queue_consumer.max_deliveries(),
+        };
 
         Ok(Self {
             channel,
             consumer,
-            reject_options,
-            ack_options,
+            acknowledge_mode: queue_consumer.acknowledge_mode(),
+            settle_options: Arc::new(settle_options),
+            recovery: None,
+            reading: None,
         })
     }
 
@@ -106,130 +322,612 @@ impl AmqpInput {
 
         Ok(consumer)
     }
+}
+
+#[async_trait]
+impl Input for AmqpInput {
+    async fn receive(&mut self) -> Result<InputData, Error> {
+        let reading = match self.reading.as_mut() {
+            Some(reading) => reading,
+            None => {
+                let delivery = self.next_delivery().await?;
+
+                self.reading.insert(tokio::spawn(read_delivery(
+                    delivery,
+                    self.channel.clone(),
+                    self.acknowledge_mode,
+                    self.settle_options.clone(),
+                )))
+            }
+        };
 
-    async fn reject_delivery(&self, delivery: Delivery, rejection_error: Error) -> Error {
-        match delivery.reject(self.reject_options).await {
-            Ok(_) => rejection_error,
-            Err(error) => Error::new(
+        let result = reading.await;
+        self.reading = None;
+
+        let (input_data, acker) = match result {
+            Ok(result) => result?,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::InternalError,
+                    format!("failed to read delivery: {}", error),
+                ))
+            }
+        };
+
+        // nothing is awaited from here on, so the request reaches the dispatch once acknowledged
+        if let Some(acker) = acker {
+            let ack_options = self.settle_options.ack_options;
+
+            tokio::spawn(async move {
+                if let Err(error) = acker.ack(ack_options).await {
+                    warn!("failed to acknowledge delivery: {}", error);
+                }
+            });
+        }
+
+        Ok(input_data)
+    }
+
+    async fn stop(&mut self) -> Result<(), Error> {
+        if let Err(error) = self
+            .channel
+            .basic_cancel(self.consumer.tag().as_str(), BasicCancelOptions::default())
+            .await
+        {
+            return Err(Error::new(
                 ErrorKind::ApiError,
-                format!("failed to reject delivery: {}", error),
-            ),
+                format!("failed to cancel consumer: {}", error),
+            ));
+        }
+
+        // closing the channel hands the deliveries which have not been acknowledged back to
+        // the broker
+        if let Err(error) = self
+            .channel
+            .close(REPLY_SUCCESS, "api dispatch is stopping")
+            .await
+        {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to close channel: {}", error),
+            ));
         }
+
+        Ok(())
     }
 }
 
-#[async_trait]
-impl Input for AmqpInput {
-    async fn receive(&mut self) -> Result<InputData, Error> {
-        let delivery = match self.consumer.try_next().await {
-            Ok(optional_delivery) => match optional_delivery {
-                Some(delivery) => delivery,
-                None => {
-                    return Err(Error::new(
+///
+/// Reads the request of the delivery and builds its replier, dead-lettering the delivery when
+/// its request cannot be handled. The deliveries acknowledged on receive are acknowledged by
+/// `receive` once it returns their request.
+///
+async fn read_delivery(
+    delivery: Delivery,
+    channel: Arc<Channel>,
+    acknowledge_mode: AmqpAcknowledgeMode,
+    settle_options: Arc<SettleOptions>,
+) -> Result<ReadDelivery, Error> {
+    let delivery = UnsettledDelivery {
+        channel: channel.clone(),
+        delivery_count: delivery_count(&delivery.properties, delivery.redelivered),
+        acker: delivery.acker,
+        data: delivery.data,
+        properties: delivery.properties,
+        settle_options: settle_options.clone(),
+    };
+
+    if let Some(max_deliveries) = settle_options.max_deliveries {
+        if delivery.delivery_count > max_deliveries {
+            return Err(delivery
+                .discard(Error::new(
+                    ErrorKind::RequestError,
+                    format!(
+                        "delivery exceeded the maximum of {} deliveries",
+                        max_deliveries
+                    ),
+                ))
+                .await);
+        }
+    }
+
+    let json_request = match std::str::from_utf8(delivery.data.as_slice()) {
+        Ok(json_request) => json_request,
+        Err(error) => {
+            return Err(delivery
+                .discard(Error::new(
+                    ErrorKind::RequestError,
+                    format!("delivery is not an utf8 string: {}", error),
+                ))
+                .await);
+        }
+    };
+
+    let request: Request = match serde_json::from_str(json_request) {
+        Ok(request) => request,
+        Err(error) => {
+            return Err(delivery
+                .discard(Error::new(
+                    ErrorKind::RequestError,
+                    format!("failed to deserialize request: {}", error),
+                ))
+                .await);
+        }
+    };
+
+    match acknowledge_mode {
+        AmqpAcknowledgeMode::OnReceive => {
+            let properties = delivery.properties;
+
+            let replier: Replier = Arc::new(move |value| {
+                let channel = channel.clone();
+                let properties = properties.clone();
+
+                Box::pin(async move { publish_reply(&channel, &properties, value).await })
+            });
+
+            Ok((InputData::new(request, replier), Some(delivery.acker)))
+        }
+        AmqpAcknowledgeMode::AfterReply => {
+            let delivery = Arc::new(delivery);
+
+            let replier: Replier = Arc::new(move |value| {
+                let delivery = delivery.clone();
+
+                Box::pin(async move { delivery.settle_after_reply(value).await })
+            });
+
+            Ok((InputData::new(request, replier), None))
+        }
+    }
+}
+
+///
+/// Delivery whose request is read but which is yet to be acknowledged, requeued, rejected or
+/// dead-lettered.
+///
+struct UnsettledDelivery {
+    channel: Arc<Channel>,
+    acker: Acker,
+    data: Vec<u8>,
+    properties: BasicProperties,
+    delivery_count: u32,
+    settle_options: Arc<SettleOptions>,
+}
+
+impl UnsettledDelivery {
+    ///
+    /// Sends the reply, then settles the delivery according to the outcome of its request. The
+    /// failed requests which are requeued are not replied, so their client only gets the reply
+    /// of the last attempt.
+    ///
+    async fn settle_after_reply(&self, value: Value) -> Result<(), Error> {
+        let settlement = settlement_of_reply(
+            &value,
+            self.settle_options.failure_policy,
+            self.settle_options.max_deliveries,
+            self.delivery_count,
+        );
+
+        if let Settlement::Requeue = settlement {
+            return self.requeue().await;
+        }
+
+        if let Err(error) = publish_reply(&self.channel, &self.properties, value).await {
+            // the reply is lost, so the request is handled again unless it is over the cap
+            if let Settlement::Acknowledge = settlement {
+                self.requeue().await?;
+            }
+
+            return Err(error);
+        }
+
+        match settlement {
+            Settlement::Acknowledge => {
+                match self.acker.ack(self.settle_options.ack_options).await {
+                    Ok(()) => Ok(()),
+                    Err(error) => Err(Error::new(
                         ErrorKind::ApiError,
-                        "consumer got an empty delivery",
-                    ));
+                        format!("failed to acknowledge delivery: {}", error),
+                    )),
                 }
+            }
+            Settlement::Requeue => Ok(()),
+            Settlement::Reject => match self.acker.reject(self.settle_options.reject_options).await
+            {
+                Ok(()) => Ok(()),
+                Err(error) => Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to reject delivery: {}", error),
+                )),
             },
+            Settlement::DeadLetter(error) => self.dead_letter(&error).await,
+        }
+    }
+
+    ///
+    /// Hands the delivery back to its queue. With a maximum of deliveries, the delivery is
+    /// republished to the tail of its queue along its count of deliveries, as classic queues do
+    /// not count them, and only requeued when the republishing fails.
+    ///
+    async fn requeue(&self) -> Result<(), Error> {
+        if self.settle_options.max_deliveries.is_some() {
+            match self.republish().await {
+                Ok(()) => {
+                    return match self.acker.ack(self.settle_options.ack_options).await {
+                        Ok(()) => Ok(()),
+                        Err(error) => Err(Error::new(
+                            ErrorKind::ApiError,
+                            format!("failed to acknowledge republished delivery: {}", error),
+                        )),
+                    };
+                }
+                Err(error) => warn!("failed to republish delivery, requeueing it: {}", error),
+            }
+        }
+
+        let options = BasicNackOptions {
+            multiple: false,
+            requeue: true,
+        };
+
+        match self.acker.nack(options).await {
+            Ok(()) => Ok(()),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to requeue delivery: {}", error),
+            )),
+        }
+    }
+
+    async fn republish(&self) -> Result<(), Error> {
+        let confirm = match self
+            .channel
+            .basic_publish(
+                "",
+                self.settle_options.queue_name.as_str(),
+                BasicPublishOptions::default(),
+                self.data.as_slice(),
+                with_delivery_attempts(self.properties.clone(), self.delivery_count),
+            )
+            .await
+        {
+            Ok(confirm) => confirm,
             Err(error) => {
                 return Err(Error::new(
                     ErrorKind::ApiError,
-                    format!("consumer got an error: {}", error),
-                ));
+                    format!("failed to republish delivery: {}", error),
+                ))
             }
         };
 
-        let json_request = match std::str::from_utf8(delivery.data.as_slice()) {
-            Ok(json_request) => json_request,
-            Err(error) => {
-                return Err(self
-                    .reject_delivery(
-                        delivery,
-                        Error::new(
-                            ErrorKind::RequestError,
-                            format!("delivery is not an utf8 string: {}", error),
-                        ),
-                    )
-                    .await);
+        match confirm.await {
+            Ok(Confirmation::Nack(_)) => Err(Error::new(
+                ErrorKind::ApiError,
+                "broker did not accept republished delivery",
+            )),
+            Ok(_) => Ok(()),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to confirm republished delivery: {}", error),
+            )),
+        }
+    }
+
+    ///
+    /// Dead-letters the delivery whose request cannot be handled, returning the reason unless
+    /// the delivery cannot be settled.
+    ///
+    async fn discard(&self, error: Error) -> Error {
+        match self.dead_letter(&error).await {
+            Ok(()) => error,
+            Err(settle_error) => settle_error,
+        }
+    }
+
+    ///
+    /// Republishes the delivery to the dead-letter exchange when there is one, falling back to
+    /// rejecting it with the consumer's reject options otherwise or when the republishing fails.
+    ///
+    async fn dead_letter(&self, dead_letter_error: &Error) -> Result<(), Error> {
+        if let Some(dead_letter) = &self.settle_options.dead_letter {
+            match try_publish_dead_letter(
+                &self.channel,
+                dead_letter,
+                self.data.as_slice(),
+                self.properties.clone(),
+                self.settle_options.queue_name.as_str(),
+                dead_letter_error,
+            )
+            .await
+            {
+                Ok(()) => {
+                    return match self.acker.ack(self.settle_options.ack_options).await {
+                        Ok(()) => Ok(()),
+                        Err(error) => Err(Error::new(
+                            ErrorKind::ApiError,
+                            format!("failed to acknowledge dead-lettered delivery: {}", error),
+                        )),
+                    };
+                }
+                Err(error) => warn!("failed to dead-letter delivery: {}", error),
             }
-        };
+        }
 
-        let request: Request = match serde_json::from_str(json_request) {
-            Ok(request) => request,
-            Err(error) => {
-                return Err(self
-                    .reject_delivery(
-                        delivery,
-                        Error::new(
-                            ErrorKind::RequestError,
-                            format!("failed to deserialize request: {}", error),
-                        ),
-                    )
-                    .await);
+        match self.acker.reject(self.settle_options.reject_options).await {
+            Ok(()) => Ok(()),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to reject delivery: {}", error),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Settlement {
+    Acknowledge,
+    Requeue,
+    Reject,
+    DeadLetter(Error),
+}
+
+///
+/// How a delivery acknowledged after its reply is settled. Failed requests which would be
+/// requeued once they reached the maximum number of deliveries are dead-lettered instead.
+///
+fn settlement_of_reply(
+    value: &Value,
+    failure_policy: AmqpFailurePolicy,
+    max_deliveries: Option<u32>,
+    delivery_count: u32,
+) -> Settlement {
+    let error = match decode_response(value.clone()) {
+        Ok(_) => return Settlement::Acknowledge,
+        Err(error) => error,
+    };
+
+    if !failure_policy.requeues(error.kind()) {
+        return Settlement::Reject;
+    }
+
+    match max_deliveries {
+        Some(max_deliveries) if delivery_count >= max_deliveries => {
+            Settlement::DeadLetter(Error::new(
+                error.kind(),
+                format!(
+                    "request failed after {} deliveries: {}",
+                    delivery_count, error.message
+                ),
+            ))
+        }
+        _ => Settlement::Requeue,
+    }
+}
+
+///
+/// Number of times the delivery has been delivered, this one included. The deliveries handled
+/// before the request was republished are read from the `x-cp-delivery-attempts` header, to
+/// which the deliveries of this message are added: quorum queues count the previous ones in the
+/// `x-delivery-count` header, whereas classic queues only flag the redelivered ones, in which
+/// case the delivery is counted as the second one.
+///
+fn delivery_count(properties: &BasicProperties, redelivered: bool) -> u32 {
+    let previous_attempts = u32_header(properties, DELIVERY_ATTEMPTS_HEADER).unwrap_or_default();
+
+    let deliveries = match u32_header(properties, DELIVERY_COUNT_HEADER) {
+        Some(previous_deliveries) => previous_deliveries.saturating_add(1),
+        None if redelivered => 2u32,
+        None => 1u32,
+    };
+
+    previous_attempts.saturating_add(deliveries)
+}
+
+fn u32_header(properties: &BasicProperties, header: &str) -> Option<u32> {
+    properties
+        .headers()
+        .as_ref()
+        .and_then(|headers| match headers.inner().get(header) {
+            Some(AMQPValue::ShortShortUInt(count)) => Some(u32::from(*count)),
+            Some(AMQPValue::ShortUInt(count)) => Some(u32::from(*count)),
+            Some(AMQPValue::LongUInt(count)) => Some(*count),
+            Some(AMQPValue::ShortShortInt(count)) => u32::try_from(*count).ok(),
+            Some(AMQPValue::ShortInt(count)) => u32::try_from(*count).ok(),
+            Some(AMQPValue::LongInt(count)) => u32::try_from(*count).ok(),
+            Some(AMQPValue::LongLongInt(count)) => u32::try_from(*count).ok(),
+            _ => None,
+        })
+}
+
+///
+/// Properties of the republished delivery, which carry the deliveries handled so far. The
+/// broker's count is left out, as it only applies to the message it was set on.
+///
+fn with_delivery_attempts(properties: BasicProperties, delivery_count: u32) -> BasicProperties {
+    let mut headers = FieldTable::default();
+
+    if let Some(previous_headers) = properties.headers() {
+        for (key, value) in previous_headers.inner() {
+            if key.as_str() != DELIVERY_COUNT_HEADER {
+                headers.insert(key.clone(), value.clone());
             }
-        };
+        }
+    }
+
+    headers.insert(
+        ShortString::from(DELIVERY_ATTEMPTS_HEADER),
+        AMQPValue::LongUInt(delivery_count),
+    );
+
+    properties.with_headers(headers)
+}
+
+async fn publish_reply(
+    channel: &Channel,
+    request_properties: &BasicProperties,
+    value: Value,
+) -> Result<(), Error> {
+    let reply_to = match request_properties.reply_to() {
+        Some(reply_to) => reply_to,
+        None => return Ok(()),
+    };
+
+    let mut response_properties =
+        BasicProperties::default().with_content_type(ShortString::from("application/json"));
+
+    if let Some(correlation_id) = request_properties.correlation_id() {
+        response_properties = response_properties.with_correlation_id(correlation_id.clone());
+    }
 
-        if let Err(error) = delivery.ack(self.ack_options).await {
-            log::warn!("failed to acknowledge delivery: {}", error);
+    let publish_options = BasicPublishOptions::default();
+    let payload = match serde_json::to_vec(&value) {
+        Ok(payload) => payload,
+        Err(error) => {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to serialize result: {}", error),
+            ));
         }
+    };
 
-        let channel = self.channel.clone();
-        let properties: BasicProperties = delivery.properties;
+    match channel
+        .basic_publish(
+            "",
+            reply_to.as_str(),
+            publish_options,
+            payload.as_slice(),
+            response_properties,
+        )
+        .await
+    {
+        Ok(_) => (),
+        Err(error) => {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to send reply: {}", error),
+            ));
+        }
+    }
 
-        let replier: Replier = Arc::new(move |value| {
-            let channel = channel.clone();
-            let properties = properties.clone();
+    Ok(())
+}
 
-            Box::pin(async move {
-                let request_properties = properties;
+#[test]
+pub fn count_deliveries_from_header_or_redelivered_flag() {
+    use lapin::types::FieldTable;
 
-                let reply_to = match request_properties.reply_to() {
-                    Some(reply_to) => reply_to,
-                    None => return Ok(()),
-                };
+    let mut headers = FieldTable::default();
+    headers.insert(
+        ShortString::from(DELIVERY_COUNT_HEADER),
+        AMQPValue::LongLongInt(2i64),
+    );
 
-                let mut response_properties = BasicProperties::default()
-                    .with_content_type(ShortString::from("application/json"));
+    assert_eq!(
+        3u32,
+        delivery_count(&BasicProperties::default().with_headers(headers), true)
+    );
+    assert_eq!(2u32, delivery_count(&BasicProperties::default(), true));
+    assert_eq!(1u32, delivery_count(&BasicProperties::default(), false));
+}
 
-                if let Some(correlation_id) = request_properties.correlation_id() {
-                    response_properties =
-                        response_properties.with_correlation_id(correlation_id.clone());
-                }
+#[test]
+pub fn dead_letter_failed_request_once_it_reaches_max_deliveries() {
+    use crate::api::shared::response::{encode_response, Response, ResponseFormat};
 
-                let publish_options = BasicPublishOptions::default();
-                let payload = match serde_json::to_vec(&value) {
-                    Ok(payload) => payload,
-                    Err(error) => {
-                        return Err(Error::new(
-                            ErrorKind::ApiError,
-                            format!("failed to serialize result: {}", error),
-                        ));
-                    }
-                };
-
-                match channel
-                    .basic_publish(
-                        "",
-                        reply_to.as_str(),
-                        publish_options,
-                        payload.as_slice(),
-                        response_properties,
-                    )
-                    .await
-                {
-                    Ok(_) => (),
-                    Err(error) => {
-                        return Err(Error::new(
-                            ErrorKind::ApiError,
-                            format!("failed to send reply: {}", error),
-                        ));
-                    }
-                }
+    let failed_reply = encode_response(
+        Response::new(Err(Error::new(ErrorKind::TimeoutError, "action timed out"))),
+        ResponseFormat::Envelope,
+    );
+    let succeeded_reply = encode_response(
+        Response::new(Ok(Value::from("done"))),
+        ResponseFormat::Envelope,
+    );
 
-                Ok(())
-            })
-        });
+    assert_eq!(
+        Settlement::Acknowledge,
+        settlement_of_reply(
+            &succeeded_reply,
+            AmqpFailurePolicy::Requeue,
+            Some(3u32),
+            3u32
+        )
+    );
+    assert_eq!(
+        Settlement::Reject,
+        settlement_of_reply(&failed_reply, AmqpFailurePolicy::Reject, Some(3u32), 1u32)
+    );
+    assert_eq!(
+        Settlement::Requeue,
+        settlement_of_reply(&failed_reply, AmqpFailurePolicy::Requeue, Some(3u32), 2u32)
+    );
+    assert_eq!(
+        Settlement::Requeue,
+        settlement_of_reply(&failed_reply, AmqpFailurePolicy::Requeue, None, 20u32)
+    );
 
-        Ok(InputData::new(request, replier))
+    match settlement_of_reply(&failed_reply, AmqpFailurePolicy::Requeue, Some(3u32), 3u32) {
+        Settlement::DeadLetter(error) => {
+            assert_eq!(ErrorKind::TimeoutError, error.kind());
+            assert_eq!(
+                "request failed after 3 deliveries: action timed out",
+                error.message
+            );
+        }
+        settlement => panic!("expected the request to be dead-lettered: {:?}", settlement),
     }
 }
+
+#[test]
+pub fn dead_letter_republished_classic_queue_delivery_once_it_reaches_max_deliveries() {
+    use crate::api::shared::response::{encode_response, Response, ResponseFormat};
+
+    let failed_reply = encode_response(
+        Response::new(Err(Error::new(ErrorKind::TimeoutError, "action timed out"))),
+        ResponseFormat::Envelope,
+    );
+
+    // classic queues never set the delivery count, so each attempt is a fresh delivery
+    let mut properties = BasicProperties::default();
+    let mut delivery_counts = Vec::new();
+
+    loop {
+        let count = delivery_count(&properties, false);
+        delivery_counts.push(count);
+
+        match settlement_of_reply(&failed_reply, AmqpFailurePolicy::Requeue, Some(4u32), count) {
+            Settlement::Requeue => properties = with_delivery_attempts(properties, count),
+            Settlement::DeadLetter(error) => {
+                assert_eq!(
+                    "request failed after 4 deliveries: action timed out",
+                    error.message
+                );
+                break;
+            }
+            settlement => panic!("unexpected settlement: {:?}", settlement),
+        }
+    }
+
+    assert_eq!(vec![1u32, 2u32, 3u32, 4u32], delivery_counts);
+
+    // a republished delivery redelivered after a crash counts both
+    assert_eq!(5u32, delivery_count(&properties, true));
+}
+
+#[test]
+pub fn leave_broker_delivery_count_out_of_republished_delivery() {
+    let mut headers = FieldTable::default();
+    headers.insert(
+        ShortString::from(DELIVERY_COUNT_HEADER),
+        AMQPValue::LongLongInt(2i64),
+    );
+
+    let properties = with_delivery_attempts(BasicProperties::default().with_headers(headers), 3u32);
+
+    assert_eq!(None, u32_header(&properties, DELIVERY_COUNT_HEADER));
+    assert_eq!(
+        Some(3u32),
+        u32_header(&properties, DELIVERY_ATTEMPTS_HEADER)
+    );
+    assert_eq!(4u32, delivery_count(&properties, false));
+}
@@ -0,0 +1,629 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use async_channel::Receiver;
+use async_trait::async_trait;
+use hyper::body::HttpBody;
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, HeaderMap, Method, Server, StatusCode};
+use log::{info, warn};
+use serde_json::Value;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::api::server::input::input::Input;
+use crate::api::server::input::input_data::InputData;
+use crate::api::server::input::replier::Replier;
+use crate::api::shared::request::Request;
+use crate::api::shared::request_header::RequestHeader;
+use crate::api::shared::response::{decode_response, encode_response, Response, ResponseFormat};
+use crate::core::error::{Error, ErrorKind};
+
+pub const DEFAULT_MAX_BODY_SIZE: usize = 1024usize * 1024usize;
+
+///
+/// Header whose value is used as the request id.
+///
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+///
+/// Header whose value is used as the requested version of the action.
+///
+pub const ACTION_VERSION_HEADER: &str = "x-action-version";
+
+const BEARER_PREFIX: &str = "Bearer ";
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+///
+/// Configuration of an `HttpInput`.
+///
+#[derive(Clone)]
+pub struct HttpInputConfig {
+    address: SocketAddr,
+    forwarded_headers: HashMap<String, String>,
+    max_body_size: usize,
+}
+
+impl HttpInputConfig {
+    pub fn new(address: SocketAddr) -> HttpInputConfig {
+        HttpInputConfig {
+            address,
+            forwarded_headers: HashMap::new(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    ///
+    /// Copies the value of the given HTTP header, when present, into the request header's extra
+    /// under the given key.
+    ///
+    pub fn with_forwarded_header(mut self, header: &str, extra_key: &str) -> HttpInputConfig {
+        self.forwarded_headers
+            .insert(header.to_lowercase(), extra_key.to_string());
+        self
+    }
+
+    ///
+    /// Maximum size, in bytes, of the requests' body. Bigger requests are rejected with
+    /// `413 Payload Too Large`.
+    ///
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> HttpInputConfig {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+struct HttpInputState {
+    sender: async_channel::Sender<InputData>,
+    forwarded_headers: HashMap<String, String>,
+    max_body_size: usize,
+}
+
+///
+/// Input which exposes the API through HTTP. Actions are requested with `POST /{action}`, whose
+/// body is the payload, or with `POST /`, whose body is a whole `Request`.
+///
+/// For `POST /{action}`, the bearer token of the `Authorization` header is used as the request
+/// token, while the `x-request-id` and `x-action-version` headers set the request id and the
+/// requested version.
+///
+pub struct HttpInput {
+    local_address: SocketAddr,
+    receiver: Receiver<InputData>,
+    shutdown_token: CancellationToken,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl HttpInput {
+    pub fn try_new(config: HttpInputConfig) -> Result<HttpInput, Error> {
+        let incoming = match AddrIncoming::bind(&config.address) {
+            Ok(incoming) => incoming,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!(
+                        "failed to bind http input to '{}': {}",
+                        config.address, error
+                    ),
+                ))
+            }
+        };
+        let local_address = incoming.local_addr();
+
+        let (sender, receiver) = async_channel::bounded::<InputData>(1usize);
+        let state = Arc::new(HttpInputState {
+            sender,
+            forwarded_headers: config.forwarded_headers,
+            max_body_size: config.max_body_size,
+        });
+
+        let make_service = make_service_fn(move |_| {
+            let state = state.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle_http_request(state.clone(), request)
+                }))
+            }
+        });
+
+        let shutdown_token = CancellationToken::new();
+        let server_shutdown_token = shutdown_token.clone();
+        let server = Server::builder(incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(async move { server_shutdown_token.cancelled().await });
+
+        let handle = tokio::spawn(async move {
+            if let Err(error) = server.await {
+                warn!("http input failed: {}", error);
+            }
+        });
+
+        info!("http input listening on '{}'", local_address);
+
+        Ok(HttpInput {
+            local_address,
+            receiver,
+            shutdown_token,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+}
+
+#[async_trait]
+impl Input for HttpInput {
+    async fn receive(&mut self) -> Result<InputData, Error> {
+        match self.receiver.recv().await {
+            Ok(input_data) => Ok(input_data),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("http input is stopped: {}", error),
+            )),
+        }
+    }
+
+    async fn stop(&mut self) -> Result<(), Error> {
+        self.shutdown_token.cancel();
+        self.receiver.close();
+
+        // dropping the requests which were not received replies them with an error
+        while self.receiver.try_recv().is_ok() {}
+
+        if let Some(handle) = self.handle.take() {
+            if let Err(error) = handle.await {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to stop http input: {}", error),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// HTTP status code replied for the errors of the given kind.
+///
+pub fn status_code_of_error_kind(kind: ErrorKind) -> StatusCode {
+    match kind {
+        ErrorKind::RequestError => StatusCode::BAD_REQUEST,
+        ErrorKind::UnauthorizedError => StatusCode::UNAUTHORIZED,
+        ErrorKind::UnknownActionError => StatusCode::NOT_FOUND,
+        ErrorKind::ValidationError => StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorKind::RateLimitedError => StatusCode::TOO_MANY_REQUESTS,
+        ErrorKind::OverloadedError => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorKind::TimeoutError => StatusCode::GATEWAY_TIMEOUT,
+        ErrorKind::ApiError
+        | ErrorKind::LogicError
+        | ErrorKind::StorageError
+        | ErrorKind::Unknown
+        | ErrorKind::InitializationError
+        | ErrorKind::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+async fn handle_http_request(
+    state: Arc<HttpInputState>,
+    http_request: hyper::Request<Body>,
+) -> Result<hyper::Response<Body>, Infallible> {
+    if http_request.method() != Method::POST {
+        return Ok(build_error_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            Error::new(
+                ErrorKind::RequestError,
+                format!("method '{}' is not allowed", http_request.method()),
+            ),
+        ));
+    }
+
+    let action = http_request
+        .uri()
+        .path()
+        .trim_start_matches('/')
+        .to_string();
+
+    if action.contains('/') {
+        return Ok(build_error_response(
+            StatusCode::NOT_FOUND,
+            Error::new(
+                ErrorKind::UnknownActionError,
+                format!(
+                    "path '{}' does not match any action",
+                    http_request.uri().path()
+                ),
+            ),
+        ));
+    }
+
+    let (parts, body) = http_request.into_parts();
+
+    let body = match read_body(body, state.max_body_size).await {
+        Ok(body) => body,
+        Err((status, error)) => return Ok(build_error_response(status, error)),
+    };
+
+    let request = match build_request(&state, action, &parts.headers, body) {
+        Ok(request) => request,
+        Err(error) => {
+            return Ok(build_error_response(
+                status_code_of_error_kind(error.kind()),
+                error,
+            ))
+        }
+    };
+
+    let (reply_sender, reply_receiver) = oneshot::channel::<Value>();
+    let reply_sender = Arc::new(Mutex::new(Some(reply_sender)));
+
+    let replier: Replier = Arc::new(move |value| {
+        let reply_sender = match reply_sender.lock() {
+            Ok(mut reply_sender) => reply_sender.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+
+        Box::pin(async move {
+            let reply_sender = match reply_sender {
+                Some(reply_sender) => reply_sender,
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::ApiError,
+                        "http request has already been replied",
+                    ))
+                }
+            };
+
+            match reply_sender.send(value) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(Error::new(
+                    ErrorKind::ApiError,
+                    "http client is no longer waiting for the reply",
+                )),
+            }
+        })
+    });
+
+    if state
+        .sender
+        .send(InputData::new(request, replier))
+        .await
+        .is_err()
+    {
+        return Ok(build_error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Error::new(ErrorKind::OverloadedError, "http input is stopping"),
+        ));
+    }
+
+    match reply_receiver.await {
+        Ok(reply) => Ok(build_reply_response(reply)),
+        Err(_) => Ok(build_error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Error::new(
+                ErrorKind::OverloadedError,
+                "request was abandoned before being replied",
+            ),
+        )),
+    }
+}
+
+async fn read_body(mut body: Body, max_body_size: usize) -> Result<Vec<u8>, (StatusCode, Error)> {
+    let mut bytes: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = body.data().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(error) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Error::new(
+                        ErrorKind::RequestError,
+                        format!("failed to read request body: {}", error),
+                    ),
+                ))
+            }
+        };
+
+        if bytes.len() + chunk.len() > max_body_size {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Error::new(
+                    ErrorKind::RequestError,
+                    format!("request body exceeds {} bytes", max_body_size),
+                ),
+            ));
+        }
+
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+fn build_request(
+    state: &HttpInputState,
+    action: String,
+    headers: &HeaderMap,
+    body: Vec<u8>,
+) -> Result<Request, Error> {
+    let mut request = if action.is_empty() {
+        match serde_json::from_slice::<Request>(body.as_slice()) {
+            Ok(request) => request,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::RequestError,
+                    format!("failed to deserialize request: {}", error),
+                ))
+            }
+        }
+    } else {
+        let payload = if body.is_empty() {
+            Value::Null
+        } else {
+            match serde_json::from_slice::<Value>(body.as_slice()) {
+                Ok(payload) => payload,
+                Err(error) => {
+                    return Err(Error::new(
+                        ErrorKind::RequestError,
+                        format!("failed to deserialize payload: {}", error),
+                    ))
+                }
+            }
+        };
+
+        let token = header_value(headers, AUTHORIZATION.as_str())
+            .map(|authorization| {
+                authorization
+                    .strip_prefix(BEARER_PREFIX)
+                    .unwrap_or(authorization)
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        let mut header = RequestHeader::new(action, token);
+
+        if let Some(version) = header_value(headers, ACTION_VERSION_HEADER) {
+            header = header.with_version(version.to_string());
+        }
+
+        if let Some(id) = header_value(headers, REQUEST_ID_HEADER) {
+            header = header.with_id(id.to_string());
+        }
+
+        Request::new(header, payload)
+    };
+
+    for (forwarded_header, extra_key) in &state.forwarded_headers {
+        if let Some(value) = header_value(headers, forwarded_header) {
+            request
+                .mut_header()
+                .add_extra(extra_key.clone(), value.to_string());
+        }
+    }
+
+    Ok(request)
+}
+
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn build_reply_response(reply: Value) -> hyper::Response<Body> {
+    let (status, retry_after) = match decode_response(reply.clone()) {
+        Ok(_) => (StatusCode::OK, None),
+        Err(error) => (
+            status_code_of_error_kind(error.kind()),
+            retry_after_seconds(&error),
+        ),
+    };
+
+    let mut response = build_json_response(status, reply);
+
+    if let Some(retry_after) = retry_after {
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, hyper::header::HeaderValue::from(retry_after));
+    }
+
+    response
+}
+
+fn retry_after_seconds(error: &Error) -> Option<u64> {
+    let retry_after_milliseconds = error
+        .details()
+        .and_then(|details| details.get("retry_after_milliseconds"))
+        .and_then(Value::as_u64)?;
+
+    Some(retry_after_milliseconds.div_ceil(1000u64))
+}
+
+fn build_error_response(status: StatusCode, error: Error) -> hyper::Response<Body> {
+    build_json_response(
+        status,
+        encode_response(Response::new(Err(error)), ResponseFormat::Envelope),
+    )
+}
+
+fn build_json_response(status: StatusCode, value: Value) -> hyper::Response<Body> {
+    let body = match serde_json::to_vec(&value) {
+        Ok(body) => body,
+        Err(error) => {
+            warn!("failed to serialize http reply: {}", error);
+            Vec::new()
+        }
+    };
+
+    let mut response = hyper::Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static(JSON_CONTENT_TYPE),
+    );
+
+    response
+}
+
+#[cfg(test)]
+pub(crate) async fn start_echo_dispatch(
+    config: HttpInputConfig,
+) -> (
+    SocketAddr,
+    CancellationToken,
+    Vec<JoinHandle<crate::api::server::dispatch::ShutdownReport>>,
+) {
+    use crate::api::server::dispatch::Dispatch;
+    use crate::api::server::input::action::Action;
+    use serde_json::json;
+
+    let input = HttpInput::try_new(config).expect("failed to start http input");
+    let local_address = input.local_address();
+
+    let echo_action: Action<()> = Action::new(
+        "echo".to_string(),
+        Arc::new(move |request, _sender| {
+            Box::pin(async move {
+                Ok(json!({
+                    "token": request.header().token(),
+                    "tenant": request.header().get_extra("tenant"),
+                    "payload": request.payload(),
+                }))
+            })
+        }),
+        Vec::new(),
+    );
+
+    let (logic_request_sender, _) = async_channel::unbounded::<()>();
+    let dispatch: Dispatch<HttpInput, ()> = Dispatch::new(
+        vec![input],
+        HashMap::from([("echo".to_string(), echo_action)]),
+        logic_request_sender,
+        Vec::new(),
+    );
+
+    let cancellation_token = CancellationToken::new();
+    let handles = dispatch.run(cancellation_token.clone()).await;
+
+    (local_address, cancellation_token, handles)
+}
+
+#[tokio::test]
+pub async fn reply_to_action_requested_through_path() {
+    let (local_address, cancellation_token, handles) = start_echo_dispatch(
+        HttpInputConfig::new("127.0.0.1:0".parse().expect("invalid address"))
+            .with_forwarded_header("X-Tenant", "tenant"),
+    )
+    .await;
+
+    let http_response = reqwest::Client::new()
+        .post(format!("http://{}/echo", local_address))
+        .bearer_auth("token")
+        .header("x-tenant", "cuplan")
+        .json(&serde_json::json!({ "name": "org" }))
+        .send()
+        .await
+        .expect("failed to send http request");
+
+    assert_eq!(StatusCode::OK.as_u16(), http_response.status().as_u16());
+
+    let reply: Value = http_response.json().await.expect("failed to read reply");
+
+    assert_eq!(
+        Ok(serde_json::json!({
+            "token": "token",
+            "tenant": "cuplan",
+            "payload": { "name": "org" },
+        })),
+        decode_response(reply)
+    );
+
+    cancellation_token.cancel();
+    futures_util::future::join_all(handles).await;
+}
+
+#[tokio::test]
+pub async fn reply_to_whole_request_posted_to_root() {
+    let (local_address, cancellation_token, handles) = start_echo_dispatch(HttpInputConfig::new(
+        "127.0.0.1:0".parse().expect("invalid address"),
+    ))
+    .await;
+
+    let request = Request::new(
+        RequestHeader::new("echo".to_string(), "token".to_string()),
+        Value::Bool(true),
+    );
+
+    let reply: Value = reqwest::Client::new()
+        .post(format!("http://{}/", local_address))
+        .json(&request)
+        .send()
+        .await
+        .expect("failed to send http request")
+        .json()
+        .await
+        .expect("failed to read reply");
+
+    assert_eq!(
+        Ok(serde_json::json!({
+            "token": "token",
+            "tenant": null,
+            "payload": true,
+        })),
+        decode_response(reply)
+    );
+
+    cancellation_token.cancel();
+    futures_util::future::join_all(handles).await;
+}
+
+#[tokio::test]
+pub async fn reply_with_status_code_of_error_kind() {
+    let (local_address, cancellation_token, handles) = start_echo_dispatch(HttpInputConfig::new(
+        "127.0.0.1:0".parse().expect("invalid address"),
+    ))
+    .await;
+
+    let http_response = reqwest::Client::new()
+        .post(format!("http://{}/unknown", local_address))
+        .send()
+        .await
+        .expect("failed to send http request");
+
+    assert_eq!(
+        StatusCode::NOT_FOUND.as_u16(),
+        http_response.status().as_u16()
+    );
+
+    let reply: Value = http_response.json().await.expect("failed to read reply");
+
+    assert_eq!(
+        Some(ErrorKind::UnknownActionError),
+        decode_response(reply).err().map(|error| error.kind())
+    );
+
+    let http_response = reqwest::Client::new()
+        .get(format!("http://{}/echo", local_address))
+        .send()
+        .await
+        .expect("failed to send http request");
+
+    assert_eq!(
+        StatusCode::METHOD_NOT_ALLOWED.as_u16(),
+        http_response.status().as_u16()
+    );
+
+    cancellation_token.cancel();
+    futures_util::future::join_all(handles).await;
+}
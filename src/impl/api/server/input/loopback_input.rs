@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use async_channel::{Receiver, Sender};
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::api::server::input::input::Input;
+use crate::api::server::input::input_data::InputData;
+use crate::api::server::input::replier::Replier;
+use crate::api::shared::request::Request;
+use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::client::input_consumer::loopback_input_consumer::LoopbackInputConsumer;
+
+///
+/// Request sent by a `LoopbackInputConsumer`, along the channel its reply is sent to and the
+/// correlation id the reply carries back. Requests without reply channel are not replied, like
+/// AMQP requests without reply-to.
+///
+pub struct LoopbackDelivery {
+    pub(crate) request: Request,
+    pub(crate) reply_to: Option<Sender<LoopbackReply>>,
+    pub(crate) correlation_id: String,
+}
+
+pub struct LoopbackReply {
+    pub(crate) correlation_id: String,
+    pub(crate) value: Value,
+}
+
+///
+/// In-memory input, backed by a channel, whose requests are sent by the `LoopbackInputConsumer`s
+/// created through `consumer`. It allows exercising a whole microservice within `cargo test`
+/// without an AMQP broker.
+///
+pub struct LoopbackInput {
+    sender: Sender<LoopbackDelivery>,
+    receiver: Receiver<LoopbackDelivery>,
+}
+
+impl LoopbackInput {
+    ///
+    /// Creates an input which buffers up to `capacity` requests which have not been received yet.
+    ///
+    pub fn new(capacity: usize) -> LoopbackInput {
+        let (sender, receiver) = async_channel::bounded::<LoopbackDelivery>(capacity.max(1usize));
+
+        LoopbackInput { sender, receiver }
+    }
+
+    ///
+    /// Consumer whose requests are received by this input. Requests without a reply within the
+    /// given timeout fail.
+    ///
+    pub fn consumer(&self, timeout_after_milliseconds: u64) -> LoopbackInputConsumer {
+        LoopbackInputConsumer::new(self.sender.clone(), timeout_after_milliseconds)
+    }
+}
+
+#[async_trait]
+impl Input for LoopbackInput {
+    async fn receive(&mut self) -> Result<InputData, Error> {
+        let delivery = match self.receiver.recv().await {
+            Ok(delivery) => delivery,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("loopback input is stopped: {}", error),
+                ))
+            }
+        };
+
+        let reply_to = delivery.reply_to;
+        let correlation_id = delivery.correlation_id;
+
+        let replier: Replier = Arc::new(move |value| {
+            let reply_to = match &reply_to {
+                Some(reply_to) => reply_to,
+                None => return Box::pin(async { Ok(()) }),
+            };
+
+            let reply = LoopbackReply {
+                correlation_id: correlation_id.clone(),
+                value,
+            };
+
+            let result = match reply_to.try_send(reply) {
+                Ok(_) => Ok(()),
+                Err(error) => Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to send reply: {}", error),
+                )),
+            };
+
+            Box::pin(async move { result })
+        });
+
+        Ok(InputData::new(delivery.request, replier))
+    }
+
+    async fn stop(&mut self) -> Result<(), Error> {
+        self.receiver.close();
+
+        // dropping the requests which were not received makes their consumers fail
+        while self.receiver.try_recv().is_ok() {}
+
+        Ok(())
+    }
+}
@@ -1 +1,5 @@
 pub mod amqp_input;
+pub mod http_input;
+pub mod loopback_input;
+pub mod unix_socket_input;
+pub mod websocket_input;
@@ -0,0 +1,499 @@
+use std::fs::{DirBuilder, Permissions};
+use std::io::ErrorKind as IoErrorKind;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_channel::{Receiver, Sender};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::{SinkExt, StreamExt};
+use log::{info, warn};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tokio_util::sync::CancellationToken;
+
+use crate::api::server::input::input::Input;
+use crate::api::server::input::input_data::InputData;
+use crate::api::server::input::replier::Replier;
+use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::shared::unix_socket_frame::{
+    decode_frame, encode_frame, UnixSocketReplyFrame, UnixSocketRequestFrame,
+};
+
+///
+/// Configuration of a `UnixSocketInput`.
+///
+#[derive(Clone)]
+pub struct UnixSocketInputConfig {
+    path: PathBuf,
+    permissions: Option<u32>,
+}
+
+impl UnixSocketInputConfig {
+    pub fn new(path: PathBuf) -> UnixSocketInputConfig {
+        UnixSocketInputConfig {
+            path,
+            permissions: None,
+        }
+    }
+
+    ///
+    /// Mode of the socket file, e.g. `0o660` so only the owner and its group can connect. The
+    /// socket is bound within a private directory and only moved to its path once its mode is
+    /// set, so it is never reachable with the default permissions.
+    ///
+    pub fn with_permissions(mut self, mode: u32) -> UnixSocketInputConfig {
+        self.permissions = Some(mode);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+}
+
+///
+/// Input which exposes the API through a Unix domain socket, e.g. to a local sidecar. Each
+/// length-delimited frame is a JSON `UnixSocketRequestFrame`, and its reply is sent back through
+/// the same connection as a `UnixSocketReplyFrame` with the same correlation id, so many
+/// requests can be multiplexed on one connection.
+///
+pub struct UnixSocketInput {
+    path: PathBuf,
+    receiver: Receiver<InputData>,
+    shutdown_token: CancellationToken,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl UnixSocketInput {
+    ///
+    /// Binds the socket at the configured path, replacing the socket file left behind by a
+    /// previous run. Sockets which still accept connections are left untouched.
+    ///
+    pub fn try_new(config: UnixSocketInputConfig) -> Result<UnixSocketInput, Error> {
+        remove_stale_socket(config.path())?;
+
+        let listener = match config.permissions {
+            Some(mode) => bind_with_permissions(config.path(), mode)?,
+            None => bind(config.path())?,
+        };
+
+        let (sender, receiver) = async_channel::bounded::<InputData>(1usize);
+        let shutdown_token = CancellationToken::new();
+
+        let handle = tokio::spawn(accept_connections(listener, sender, shutdown_token.clone()));
+
+        info!(
+            "unix socket input listening on '{}'",
+            config.path().display()
+        );
+
+        Ok(UnixSocketInput {
+            path: config.path,
+            receiver,
+            shutdown_token,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+}
+
+#[async_trait]
+impl Input for UnixSocketInput {
+    async fn receive(&mut self) -> Result<InputData, Error> {
+        match self.receiver.recv().await {
+            Ok(input_data) => Ok(input_data),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("unix socket input is stopped: {}", error),
+            )),
+        }
+    }
+
+    async fn stop(&mut self) -> Result<(), Error> {
+        self.shutdown_token.cancel();
+        self.receiver.close();
+
+        while self.receiver.try_recv().is_ok() {}
+
+        if let Some(handle) = self.handle.take() {
+            if let Err(error) = handle.await {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to stop unix socket input: {}", error),
+                ));
+            }
+        }
+
+        if let Err(error) = std::fs::remove_file(self.path.as_path()) {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!(
+                    "failed to remove socket '{}': {}",
+                    self.path.display(),
+                    error
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn remove_stale_socket(path: &Path) -> Result<(), Error> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => (),
+        _ => return Ok(()),
+    }
+
+    // a socket nobody listens to anymore refuses the connections
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!(
+                    "socket '{}' is already in use by another process",
+                    path.display()
+                ),
+            ))
+        }
+        Err(error) if error.kind() == IoErrorKind::ConnectionRefused => (),
+        Err(error) => {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to probe socket '{}': {}", path.display(), error),
+            ))
+        }
+    }
+
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(error) => Err(Error::new(
+            ErrorKind::ApiError,
+            format!(
+                "failed to remove stale socket '{}': {}",
+                path.display(),
+                error
+            ),
+        )),
+    }
+}
+
+fn bind(path: &Path) -> Result<UnixListener, Error> {
+    match UnixListener::bind(path) {
+        Ok(listener) => Ok(listener),
+        Err(error) => Err(Error::new(
+            ErrorKind::ApiError,
+            format!(
+                "failed to bind unix socket input to '{}': {}",
+                path.display(),
+                error
+            ),
+        )),
+    }
+}
+
+fn bind_with_permissions(path: &Path, mode: u32) -> Result<UnixListener, Error> {
+    let file_name = match path.file_name() {
+        Some(file_name) => file_name.to_string_lossy(),
+        None => {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!("socket path '{}' has no file name", path.display()),
+            ))
+        }
+    };
+
+    // the directory is created next to the socket so the socket can be renamed into place
+    let private_directory = path.with_file_name(format!(".{}.{}", file_name, uuid::Uuid::new_v4()));
+
+    if let Err(error) = DirBuilder::new()
+        .mode(0o700)
+        .create(private_directory.as_path())
+    {
+        return Err(Error::new(
+            ErrorKind::ApiError,
+            format!(
+                "failed to create directory '{}' to bind the socket: {}",
+                private_directory.display(),
+                error
+            ),
+        ));
+    }
+
+    let result = bind_in_private_directory(private_directory.as_path(), path, mode);
+
+    if let Err(error) = std::fs::remove_dir_all(private_directory.as_path()) {
+        warn!(
+            "failed to remove directory '{}': {}",
+            private_directory.display(),
+            error
+        );
+    }
+
+    result
+}
+
+fn bind_in_private_directory(
+    private_directory: &Path,
+    path: &Path,
+    mode: u32,
+) -> Result<UnixListener, Error> {
+    let private_path = private_directory.join("socket");
+    let listener = bind(private_path.as_path())?;
+
+    if let Err(error) =
+        std::fs::set_permissions(private_path.as_path(), Permissions::from_mode(mode))
+    {
+        return Err(Error::new(
+            ErrorKind::ApiError,
+            format!(
+                "failed to set permissions of socket '{}': {}",
+                path.display(),
+                error
+            ),
+        ));
+    }
+
+    if let Err(error) = std::fs::rename(private_path.as_path(), path) {
+        return Err(Error::new(
+            ErrorKind::ApiError,
+            format!("failed to move socket to '{}': {}", path.display(), error),
+        ));
+    }
+
+    Ok(listener)
+}
+
+async fn accept_connections(
+    listener: UnixListener,
+    sender: Sender<InputData>,
+    shutdown_token: CancellationToken,
+) {
+    let mut connections = tokio::task::JoinSet::new();
+
+    loop {
+        let stream = tokio::select! {
+            _ = shutdown_token.cancelled() => break,
+            result = listener.accept() => match result {
+                Ok((stream, _)) => stream,
+                Err(error) => {
+                    warn!("failed to accept unix socket connection: {}", error);
+                    continue;
+                }
+            },
+        };
+
+        while connections.try_join_next().is_some() {}
+
+        connections.spawn(handle_connection(
+            stream,
+            sender.clone(),
+            shutdown_token.clone(),
+        ));
+    }
+
+    while connections.join_next().await.is_some() {}
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    sender: Sender<InputData>,
+    shutdown_token: CancellationToken,
+) {
+    let (mut sink, mut stream) = Framed::new(stream, LengthDelimitedCodec::new()).split();
+    let (reply_sender, mut reply_receiver) = unbounded_channel::<Bytes>();
+
+    loop {
+        tokio::select! {
+            _ = shutdown_token.cancelled() => {
+                // the dispatch has replied its requests by now, so their replies are queued
+                while let Ok(reply) = reply_receiver.try_recv() {
+                    if let Err(error) = sink.send(reply).await {
+                        warn!("failed to send unix socket reply: {}", error);
+                        break;
+                    }
+                }
+
+                break;
+            }
+            frame = stream.next() => {
+                let frame = match frame {
+                    Some(Ok(frame)) => frame,
+                    Some(Err(error)) => {
+                        warn!("failed to read unix socket frame: {}", error);
+                        break;
+                    }
+                    None => break,
+                };
+
+                let request_frame = match decode_frame::<UnixSocketRequestFrame>(&frame) {
+                    Ok(request_frame) => request_frame,
+                    Err(error) => {
+                        // without a correlation id the error cannot be replied
+                        warn!("dropping unix socket frame: {}", error);
+                        continue;
+                    }
+                };
+
+                let replier = build_replier(request_frame.correlation_id, reply_sender.clone());
+
+                if sender
+                    .send(InputData::new(request_frame.request, replier))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Some(reply) = reply_receiver.recv() => {
+                if let Err(error) = sink.send(reply).await {
+                    warn!("failed to send unix socket reply: {}", error);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn build_replier(correlation_id: String, reply_sender: UnboundedSender<Bytes>) -> Replier {
+    Arc::new(move |value| {
+        let reply_frame = UnixSocketReplyFrame {
+            correlation_id: correlation_id.clone(),
+            response: value,
+        };
+
+        let result = match encode_frame(&reply_frame) {
+            Ok(bytes) => match reply_sender.send(bytes) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(Error::new(
+                    ErrorKind::ApiError,
+                    "unix socket connection is closed",
+                )),
+            },
+            Err(error) => Err(error),
+        };
+
+        Box::pin(async move { result })
+    })
+}
+
+#[cfg(test)]
+pub(crate) fn unix_socket_test_path() -> PathBuf {
+    std::env::temp_dir().join(format!("cp-microservice-{}.sock", uuid::Uuid::new_v4()))
+}
+
+#[tokio::test]
+pub async fn apply_configured_permissions_to_socket() {
+    let path = unix_socket_test_path();
+    let mut input =
+        UnixSocketInput::try_new(UnixSocketInputConfig::new(path.clone()).with_permissions(0o600))
+            .expect("failed to start unix socket input");
+
+    let mode = std::fs::metadata(path.as_path())
+        .expect("failed to read socket metadata")
+        .permissions()
+        .mode();
+
+    assert_eq!(0o600, mode & 0o777);
+
+    input
+        .stop()
+        .await
+        .expect("failed to stop unix socket input");
+
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+pub async fn keep_socket_of_running_input() {
+    let path = unix_socket_test_path();
+    let mut input = UnixSocketInput::try_new(UnixSocketInputConfig::new(path.clone()))
+        .expect("failed to start unix socket input");
+
+    let error = match UnixSocketInput::try_new(UnixSocketInputConfig::new(path.clone())) {
+        Ok(_) => panic!("expected the socket in use to be kept"),
+        Err(error) => error,
+    };
+
+    assert_eq!(ErrorKind::ApiError, error.kind());
+    assert!(std::os::unix::net::UnixStream::connect(path.as_path()).is_ok());
+
+    input
+        .stop()
+        .await
+        .expect("failed to stop unix socket input");
+}
+
+#[tokio::test]
+pub async fn replace_stale_socket() {
+    let path = unix_socket_test_path();
+    drop(std::os::unix::net::UnixListener::bind(path.as_path()).expect("failed to bind socket"));
+
+    let mut input = UnixSocketInput::try_new(UnixSocketInputConfig::new(path.clone()))
+        .expect("failed to replace stale socket");
+
+    assert!(std::os::unix::net::UnixStream::connect(path.as_path()).is_ok());
+
+    input
+        .stop()
+        .await
+        .expect("failed to stop unix socket input");
+}
+
+#[tokio::test]
+pub async fn send_pending_replies_before_closing_on_shutdown() {
+    use serde_json::Value;
+
+    use crate::api::shared::request::Request;
+    use crate::api::shared::request_header::RequestHeader;
+
+    let path = unix_socket_test_path();
+    let mut input = UnixSocketInput::try_new(UnixSocketInputConfig::new(path.clone()))
+        .expect("failed to start unix socket input");
+
+    let stream = UnixStream::connect(path.as_path())
+        .await
+        .expect("failed to connect unix socket");
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+
+    let request_frame = UnixSocketRequestFrame {
+        correlation_id: "pending".to_string(),
+        request: Request::new(
+            RequestHeader::new("echo".to_string(), "token".to_string()),
+            Value::Null,
+        ),
+    };
+    framed
+        .send(encode_frame(&request_frame).expect("failed to encode frame"))
+        .await
+        .expect("failed to send request");
+
+    let input_data = input.receive().await.expect("failed to receive request");
+
+    // the reply is queued right before the input is stopped, as the dispatch does when draining
+    (input_data.replier)(Value::from("done"))
+        .await
+        .expect("failed to reply");
+    input
+        .stop()
+        .await
+        .expect("failed to stop unix socket input");
+
+    let frame = match framed.next().await {
+        Some(Ok(frame)) => frame,
+        other => panic!("expected the reply before closing, got: {:?}", other),
+    };
+    let reply_frame =
+        decode_frame::<UnixSocketReplyFrame>(&frame).expect("failed to decode reply frame");
+
+    assert_eq!("pending", reply_frame.correlation_id);
+    assert_eq!(Value::from("done"), reply_frame.response);
+    assert!(framed.next().await.is_none());
+}
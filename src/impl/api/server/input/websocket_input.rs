@@ -0,0 +1,691 @@
+use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_channel::{Receiver, Sender};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use log::{info, warn};
+use percent_encoding::percent_decode_str;
+use serde_json::Value;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+
+use crate::api::server::input::connection_authenticator::ConnectionAuthenticator;
+use crate::api::server::input::input::Input;
+use crate::api::server::input::input_data::InputData;
+use crate::api::server::input::replier::Replier;
+use crate::api::shared::response::{decode_response, encode_response, ResponseFormat};
+use crate::core::error::{Error, ErrorKind};
+
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30u64);
+pub const DEFAULT_KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(90u64);
+
+///
+/// Query parameter of the handshake holding the connection token, for the clients which cannot
+/// set the `Authorization` header, e.g. browsers.
+///
+pub const TOKEN_QUERY_PARAMETER: &str = "token";
+
+const BEARER_PREFIX: &str = "Bearer ";
+
+///
+/// Configuration of a `WebSocketInput`.
+///
+#[derive(Clone)]
+pub struct WebSocketInputConfig {
+    address: SocketAddr,
+    authenticator: Option<Arc<dyn ConnectionAuthenticator + Send + Sync>>,
+    keepalive_interval: Duration,
+    keepalive_timeout: Duration,
+}
+
+impl WebSocketInputConfig {
+    pub fn new(address: SocketAddr) -> WebSocketInputConfig {
+        WebSocketInputConfig {
+            address,
+            authenticator: None,
+            keepalive_interval: DEFAULT_KEEPALIVE_INTERVAL,
+            keepalive_timeout: DEFAULT_KEEPALIVE_TIMEOUT,
+        }
+    }
+
+    ///
+    /// Authenticates the token of each connection once it is opened, connections whose token
+    /// is rejected are closed with the policy violation code.
+    ///
+    pub fn with_authenticator(
+        mut self,
+        authenticator: Arc<dyn ConnectionAuthenticator + Send + Sync>,
+    ) -> WebSocketInputConfig {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    ///
+    /// Interval at which pings are sent to the clients.
+    ///
+    pub fn with_keepalive_interval(mut self, keepalive_interval: Duration) -> WebSocketInputConfig {
+        self.keepalive_interval = keepalive_interval;
+        self
+    }
+
+    ///
+    /// Time without receiving any frame, pongs included, after which a connection is closed.
+    ///
+    pub fn with_keepalive_timeout(mut self, keepalive_timeout: Duration) -> WebSocketInputConfig {
+        self.keepalive_timeout = keepalive_timeout;
+        self
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+///
+/// Input which exposes the API through WebSocket connections. Each text frame is a `Request`
+/// whose header has an id chosen by the client, and its reply is sent back through the same
+/// connection as a text frame with that id as `request_id`. Many requests may be in flight per
+/// connection. Replies are always sent as `Response` envelopes, even when the dispatch replies
+/// with the legacy format, since clients need the id to match each reply to its request.
+///
+/// The connection token is taken from the `Authorization` header of the handshake or from its
+/// `token` query parameter, and replaces the token of every request of the connection.
+///
+pub struct WebSocketInput {
+    local_address: SocketAddr,
+    receiver: Receiver<InputData>,
+    shutdown_token: CancellationToken,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WebSocketInput {
+    pub async fn try_new(config: WebSocketInputConfig) -> Result<WebSocketInput, Error> {
+        let listener = match TcpListener::bind(config.address).await {
+            Ok(listener) => listener,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!(
+                        "failed to bind websocket input to '{}': {}",
+                        config.address, error
+                    ),
+                ))
+            }
+        };
+
+        let local_address = match listener.local_addr() {
+            Ok(local_address) => local_address,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to get websocket input address: {}", error),
+                ))
+            }
+        };
+
+        let (sender, receiver) = async_channel::bounded::<InputData>(1usize);
+        let shutdown_token = CancellationToken::new();
+
+        let handle = tokio::spawn(accept_connections(
+            listener,
+            sender,
+            config,
+            shutdown_token.clone(),
+        ));
+
+        info!("websocket input listening on '{}'", local_address);
+
+        Ok(WebSocketInput {
+            local_address,
+            receiver,
+            shutdown_token,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+}
+
+#[async_trait]
+impl Input for WebSocketInput {
+    async fn receive(&mut self) -> Result<InputData, Error> {
+        match self.receiver.recv().await {
+            Ok(input_data) => Ok(input_data),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("websocket input is stopped: {}", error),
+            )),
+        }
+    }
+
+    async fn stop(&mut self) -> Result<(), Error> {
+        self.shutdown_token.cancel();
+        self.receiver.close();
+
+        while self.receiver.try_recv().is_ok() {}
+
+        if let Some(handle) = self.handle.take() {
+            if let Err(error) = handle.await {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to stop websocket input: {}", error),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn accept_connections(
+    listener: TcpListener,
+    sender: Sender<InputData>,
+    config: WebSocketInputConfig,
+    shutdown_token: CancellationToken,
+) {
+    let mut connections = tokio::task::JoinSet::new();
+
+    loop {
+        let stream = tokio::select! {
+            _ = shutdown_token.cancelled() => break,
+            result = listener.accept() => match result {
+                Ok((stream, _)) => stream,
+                Err(error) => {
+                    warn!("failed to accept websocket connection: {}", error);
+                    continue;
+                }
+            },
+        };
+
+        while connections.try_join_next().is_some() {}
+
+        connections.spawn(handle_connection(
+            stream,
+            sender.clone(),
+            config.clone(),
+            shutdown_token.clone(),
+        ));
+    }
+
+    while connections.join_next().await.is_some() {}
+}
+
+// the error response of the handshake callback is defined by tungstenite
+#[allow(clippy::result_large_err)]
+async fn handle_connection(
+    stream: TcpStream,
+    sender: Sender<InputData>,
+    config: WebSocketInputConfig,
+    shutdown_token: CancellationToken,
+) {
+    let mut token: Option<String> = None;
+
+    let websocket = match tokio_tungstenite::accept_hdr_async(
+        stream,
+        |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+            token = handshake_token(request);
+            Ok(response)
+        },
+    )
+    .await
+    {
+        Ok(websocket) => websocket,
+        Err(error) => {
+            warn!("failed websocket handshake: {}", error);
+            return;
+        }
+    };
+
+    let (mut sink, mut stream) = websocket.split();
+
+    if let Some(authenticator) = &config.authenticator {
+        if let Err(error) = authenticator
+            .authenticate(token.as_deref().unwrap_or_default())
+            .await
+        {
+            let close_frame = CloseFrame {
+                code: CloseCode::Policy,
+                reason: Cow::Owned(error.message),
+            };
+
+            if let Err(error) = sink.send(Message::Close(Some(close_frame))).await {
+                warn!(
+                    "failed to close unauthenticated websocket connection: {}",
+                    error
+                );
+            }
+
+            return;
+        }
+    }
+
+    let (reply_sender, mut reply_receiver) = unbounded_channel::<Message>();
+    let mut keepalive = tokio::time::interval(config.keepalive_interval);
+    let mut last_received_at = Instant::now();
+
+    loop {
+        tokio::select! {
+            _ = shutdown_token.cancelled() => {
+                // the dispatch has replied its requests by now, so their replies are queued
+                while let Ok(reply) = reply_receiver.try_recv() {
+                    if let Err(error) = sink.send(reply).await {
+                        warn!("failed to send websocket reply: {}", error);
+                        break;
+                    }
+                }
+
+                if let Err(error) = sink.send(Message::Close(None)).await {
+                    warn!("failed to close websocket connection: {}", error);
+                }
+
+                break;
+            }
+            message = stream.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    Some(Err(error)) => {
+                        warn!("failed to read websocket frame: {}", error);
+                        break;
+                    }
+                    None => break,
+                };
+
+                last_received_at = Instant::now();
+
+                match message {
+                    Message::Text(text) => {
+                        forward_request(text.as_str(), token.as_deref(), &sender, &reply_sender)
+                            .await
+                    }
+                    Message::Close(_) => break,
+                    _ => (),
+                }
+            }
+            Some(reply) = reply_receiver.recv() => {
+                if let Err(error) = sink.send(reply).await {
+                    warn!("failed to send websocket reply: {}", error);
+                    break;
+                }
+            }
+            _ = keepalive.tick() => {
+                if last_received_at.elapsed() >= config.keepalive_timeout {
+                    info!("closing websocket connection which missed its keepalive");
+                    break;
+                }
+
+                if let Err(error) = sink.send(Message::Ping(Vec::new())).await {
+                    warn!("failed to ping websocket connection: {}", error);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn handshake_token(request: &Request) -> Option<String> {
+    if let Some(authorization) = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+    {
+        return Some(
+            authorization
+                .strip_prefix(BEARER_PREFIX)
+                .unwrap_or(authorization)
+                .to_string(),
+        );
+    }
+
+    request.uri().query().and_then(|query| {
+        query.split('&').find_map(|parameter| {
+            parameter
+                .strip_prefix(TOKEN_QUERY_PARAMETER)
+                .and_then(|value| value.strip_prefix('='))
+                .map(|value| percent_decode_str(value).decode_utf8_lossy().to_string())
+        })
+    })
+}
+
+async fn forward_request(
+    text: &str,
+    token: Option<&str>,
+    sender: &Sender<InputData>,
+    reply_sender: &UnboundedSender<Message>,
+) {
+    let mut request = match serde_json::from_str::<crate::api::shared::request::Request>(text) {
+        Ok(request) => request,
+        Err(error) => {
+            reply_error(
+                reply_sender,
+                Error::new(
+                    ErrorKind::RequestError,
+                    format!("failed to deserialize request: {}", error),
+                ),
+            );
+            return;
+        }
+    };
+
+    if request.header().id().is_none() {
+        reply_error(
+            reply_sender,
+            Error::new(
+                ErrorKind::RequestError,
+                "requests sent through a websocket must have an id",
+            ),
+        );
+        return;
+    }
+
+    if let Some(token) = token {
+        request.mut_header().set_token(token.to_string());
+    }
+
+    let request_id = request.header().id().unwrap_or_default().to_string();
+    let connection_reply_sender = reply_sender.clone();
+    let replier: Replier = Arc::new(move |value: Value| {
+        let result = match serde_json::to_string(&envelope_reply(value, &request_id)) {
+            Ok(text) => match connection_reply_sender.send(Message::Text(text)) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(Error::new(
+                    ErrorKind::ApiError,
+                    "websocket connection is closed",
+                )),
+            },
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to serialize result: {}", error),
+            )),
+        };
+
+        Box::pin(async move { result })
+    });
+
+    if sender.send(InputData::new(request, replier)).await.is_err() {
+        reply_error(
+            reply_sender,
+            Error::new(ErrorKind::OverloadedError, "websocket input is stopping"),
+        );
+    }
+}
+
+///
+/// Reply as a `Response` envelope carrying the request id, replies in the legacy format are
+/// wrapped into one.
+///
+fn envelope_reply(value: Value, request_id: &str) -> Value {
+    let is_envelope = value
+        .as_object()
+        .map(|object| object.contains_key("version") && object.contains_key("status"))
+        .unwrap_or(false);
+
+    if is_envelope {
+        return value;
+    }
+
+    encode_response(
+        crate::api::shared::response::Response::new(decode_response(value))
+            .with_request_id(Some(request_id.to_string())),
+        ResponseFormat::Envelope,
+    )
+}
+
+fn reply_error(reply_sender: &UnboundedSender<Message>, error: Error) {
+    let reply = encode_response(
+        crate::api::shared::response::Response::new(Err(error)),
+        ResponseFormat::Envelope,
+    );
+
+    if reply_sender.send(Message::Text(reply.to_string())).is_err() {
+        warn!("failed to reply to websocket request");
+    }
+}
+
+#[cfg(test)]
+async fn start_websocket_echo_dispatch(
+    config: WebSocketInputConfig,
+) -> (
+    SocketAddr,
+    CancellationToken,
+    Vec<JoinHandle<crate::api::server::dispatch::ShutdownReport>>,
+) {
+    use std::collections::HashMap;
+
+    use crate::api::server::dispatch::Dispatch;
+    use crate::api::server::input::action::Action;
+
+    let input = WebSocketInput::try_new(config)
+        .await
+        .expect("failed to start websocket input");
+    let local_address = input.local_address();
+
+    let echo_action: Action<()> = Action::new(
+        "echo".to_string(),
+        Arc::new(move |request, _sender| {
+            Box::pin(async move {
+                let delay = request.payload().as_u64().unwrap_or_default();
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+
+                Ok(Value::String(request.header().token().to_string()))
+            })
+        }),
+        Vec::new(),
+    );
+
+    let (logic_request_sender, _) = async_channel::unbounded::<()>();
+    let dispatch: Dispatch<WebSocketInput, ()> = Dispatch::new(
+        vec![input],
+        HashMap::from([("echo".to_string(), echo_action)]),
+        logic_request_sender,
+        Vec::new(),
+    );
+
+    let cancellation_token = CancellationToken::new();
+    let handles = dispatch.run(cancellation_token.clone()).await;
+
+    (local_address, cancellation_token, handles)
+}
+
+#[cfg(test)]
+pub struct RejectingAuthenticator {}
+
+#[cfg(test)]
+#[async_trait]
+impl ConnectionAuthenticator for RejectingAuthenticator {
+    async fn authenticate(&self, _token: &str) -> Result<(), Error> {
+        Err(Error::new(ErrorKind::UnauthorizedError, "token is revoked"))
+    }
+}
+
+#[tokio::test]
+pub async fn reply_to_concurrent_requests_through_same_connection() {
+    use crate::api::shared::request_header::RequestHeader;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let (local_address, cancellation_token, handles) = start_websocket_echo_dispatch(
+        WebSocketInputConfig::new("127.0.0.1:0".parse().expect("invalid address")),
+    )
+    .await;
+
+    let mut handshake = format!("ws://{}/", local_address)
+        .into_client_request()
+        .expect("invalid websocket request");
+    handshake.headers_mut().insert(
+        "authorization",
+        "Bearer connection-token".parse().expect("invalid header"),
+    );
+
+    let (mut websocket, _) = tokio_tungstenite::connect_async(handshake)
+        .await
+        .expect("failed to connect websocket");
+
+    for (id, delay) in [("slow", 200u64), ("fast", 0u64)] {
+        let request = crate::api::shared::request::Request::new(
+            RequestHeader::new("echo".to_string(), "request-token".to_string())
+                .with_id(id.to_string()),
+            Value::from(delay),
+        );
+
+        websocket
+            .send(Message::Text(
+                serde_json::to_string(&request).expect("failed to serialize request"),
+            ))
+            .await
+            .expect("failed to send request");
+    }
+
+    let mut replied_ids: Vec<String> = Vec::new();
+
+    while replied_ids.len() < 2usize {
+        let text = match websocket.next().await {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(_)) => continue,
+            other => panic!("unexpected websocket frame: {:?}", other),
+        };
+
+        let response: crate::api::shared::response::Response =
+            serde_json::from_str(text.as_str()).expect("failed to deserialize reply");
+
+        assert_eq!(&Value::from("connection-token"), response.payload());
+        replied_ids.push(response.request_id().unwrap_or_default().to_string());
+    }
+
+    assert_eq!(vec!["fast".to_string(), "slow".to_string()], replied_ids);
+
+    cancellation_token.cancel();
+    futures_util::future::join_all(handles).await;
+}
+
+#[tokio::test]
+pub async fn send_pending_replies_before_closing_on_shutdown() {
+    use crate::api::shared::request_header::RequestHeader;
+
+    let (local_address, cancellation_token, handles) = start_websocket_echo_dispatch(
+        WebSocketInputConfig::new("127.0.0.1:0".parse().expect("invalid address")),
+    )
+    .await;
+
+    let (mut websocket, _) = tokio_tungstenite::connect_async(format!("ws://{}/", local_address))
+        .await
+        .expect("failed to connect websocket");
+
+    let request = crate::api::shared::request::Request::new(
+        RequestHeader::new("echo".to_string(), "token".to_string()).with_id("slow".to_string()),
+        Value::from(200u64),
+    );
+
+    websocket
+        .send(Message::Text(
+            serde_json::to_string(&request).expect("failed to serialize request"),
+        ))
+        .await
+        .expect("failed to send request");
+
+    tokio::time::sleep(Duration::from_millis(50u64)).await;
+    cancellation_token.cancel();
+
+    let text = loop {
+        match websocket.next().await {
+            Some(Ok(Message::Text(text))) => break text,
+            Some(Ok(Message::Ping(_))) => continue,
+            other => panic!("expected the reply before closing, got: {:?}", other),
+        }
+    };
+    let response: crate::api::shared::response::Response =
+        serde_json::from_str(text.as_str()).expect("failed to deserialize reply");
+
+    assert_eq!(Some("slow"), response.request_id());
+    let frame = loop {
+        match websocket.next().await {
+            Some(Ok(Message::Ping(_))) => continue,
+            frame => break frame,
+        }
+    };
+    assert!(matches!(frame, Some(Ok(Message::Close(_)))));
+
+    futures_util::future::join_all(handles).await;
+}
+
+#[test]
+pub fn send_request_id_within_legacy_replies() {
+    let legacy_reply = encode_response(
+        crate::api::shared::response::Response::new(Ok(Value::from("created"))),
+        ResponseFormat::Legacy,
+    );
+
+    let response: crate::api::shared::response::Response =
+        serde_json::from_value(envelope_reply(legacy_reply, "1"))
+            .expect("failed to deserialize reply");
+
+    assert_eq!(Some("1"), response.request_id());
+    assert_eq!(&Value::from("created"), response.payload());
+}
+
+#[test]
+pub fn percent_decode_query_token() {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let request = "ws://127.0.0.1/?version=1&token=a%2Bb%2Fc%3D%3D"
+        .into_client_request()
+        .expect("invalid websocket request");
+
+    assert_eq!(Some("a+b/c==".to_string()), handshake_token(&request));
+}
+
+#[tokio::test]
+pub async fn close_connection_with_rejected_token() {
+    let (local_address, cancellation_token, handles) = start_websocket_echo_dispatch(
+        WebSocketInputConfig::new("127.0.0.1:0".parse().expect("invalid address"))
+            .with_authenticator(Arc::new(RejectingAuthenticator {})),
+    )
+    .await;
+
+    let (mut websocket, _) =
+        tokio_tungstenite::connect_async(format!("ws://{}/?token=revoked", local_address))
+            .await
+            .expect("failed to connect websocket");
+
+    match websocket.next().await {
+        Some(Ok(Message::Close(Some(close_frame)))) => {
+            assert_eq!(CloseCode::Policy, close_frame.code);
+            assert_eq!("token is revoked", close_frame.reason);
+        }
+        other => panic!("expected a close frame, got: {:?}", other),
+    }
+
+    cancellation_token.cancel();
+    futures_util::future::join_all(handles).await;
+}
+
+#[tokio::test]
+pub async fn ping_connections_to_keep_them_alive() {
+    let (local_address, cancellation_token, handles) = start_websocket_echo_dispatch(
+        WebSocketInputConfig::new("127.0.0.1:0".parse().expect("invalid address"))
+            .with_keepalive_interval(Duration::from_millis(20u64)),
+    )
+    .await;
+
+    let (mut websocket, _) = tokio_tungstenite::connect_async(format!("ws://{}/", local_address))
+        .await
+        .expect("failed to connect websocket");
+
+    let frame = tokio::time::timeout(Duration::from_millis(200u64), websocket.next())
+        .await
+        .expect("timed out waiting for a ping");
+
+    assert!(matches!(frame, Some(Ok(Message::Ping(_)))));
+
+    cancellation_token.cancel();
+    futures_util::future::join_all(handles).await;
+}
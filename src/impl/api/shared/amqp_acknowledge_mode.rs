@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+///
+/// When the deliveries of a consumed queue are acknowledged.
+///
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum AmqpAcknowledgeMode {
+    ///
+    /// Deliveries are acknowledged as soon as their request is read, so a crash while the action
+    /// runs loses the request.
+    ///
+    #[default]
+    OnReceive,
+    ///
+    /// Deliveries are acknowledged once their reply is sent, and settled according to the
+    /// failure policy when the request fails.
+    ///
+    AfterReply,
+}
@@ -1,8 +1,72 @@
 use serde::{Deserialize, Serialize};
 
+use crate::r#impl::api::shared::amqp_dead_letter::AmqpDeadLetter;
+use crate::r#impl::api::shared::amqp_exchange::AmqpExchange;
+use crate::r#impl::api::shared::amqp_queue_binding::AmqpQueueBinding;
 use crate::r#impl::api::shared::amqp_queue_consumer::AmqpQueueConsumer;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct AmqpApiEntry {
     pub amqp_queue_consumer: AmqpQueueConsumer,
+    ///
+    /// Exchanges declared before the queue, e.g. the ones the queue is bound to or the ones the
+    /// service publishes its events to.
+    ///
+    #[serde(default)]
+    pub exchanges: Vec<AmqpExchange>,
+    ///
+    /// Bindings of the consumed queue, declared once the queue and the exchanges are.
+    ///
+    #[serde(default)]
+    pub bindings: Vec<AmqpQueueBinding>,
+    ///
+    /// Exchange and queue the requests which cannot be read are republished to, instead of
+    /// being rejected.
+    ///
+    #[serde(default)]
+    pub dead_letter: Option<AmqpDeadLetter>,
+}
+
+#[test]
+pub fn deserialize_entry_without_exchanges_nor_bindings() {
+    let entry: AmqpApiEntry = serde_json::from_str(
+        r#"{
+            "amqp_queue_consumer": {
+                "queue": {
+                    "name": "organization",
+                    "declare": {
+                        "options": {
+                            "passive": false,
+                            "durable": false,
+                            "exclusive": false,
+                            "auto_delete": true,
+                            "nowait": false
+                        },
+                        "arguments": {}
+                    }
+                },
+                "qos": { "prefetch_count": 10, "options": { "global": true } },
+                "consume": {
+                    "options": {
+                        "no_local": false,
+                        "no_ack": false,
+                        "exclusive": false,
+                        "nowait": false
+                    },
+                    "arguments": {}
+                },
+                "acknowledge": { "multiple": false },
+                "reject": { "requeue": false }
+            },
+            "bindings": [
+                { "exchange": "organization_events", "routing_key": "org.*" }
+            ]
+        }"#,
+    )
+    .expect("failed to deserialize amqp api entry");
+
+    assert!(entry.exchanges.is_empty());
+    assert!(entry.dead_letter.is_none());
+    assert_eq!("organization_events", entry.bindings[0].exchange());
+    assert_eq!("org.*", entry.bindings[0].routing_key());
 }
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use lapin::Channel;
+use log::{info, warn};
+use multiple_connections_lapin_wrapper::{
+    amqp_wrapper::AmqpWrapper, config::amqp_connect_config::AmqpConnectConfig,
+};
+use tokio::sync::Mutex;
+
+use crate::core::backoff::Backoff;
+use crate::core::error::{Error, ErrorKind};
+use crate::core::health::{health, HealthState};
+
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500u64);
+
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30u64);
+
+///
+/// Hands out channels of the broker through an `AmqpWrapper`, replacing the wrapper with a new
+/// one, and so connecting again, whenever it fails to create a channel. The state of the
+/// connection is reported to the service's health under the connector's name, by the users of
+/// the channels once they are set up. Each user, e.g. an input, reports its own state, and the
+/// connection is only reported as up while none of them lost it.
+///
+pub struct AmqpConnector {
+    name: String,
+    connect_config: AmqpConnectConfig,
+    wrapper: Mutex<AmqpWrapper>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    user_states: std::sync::Mutex<HashMap<usize, HealthState>>,
+    next_user: AtomicUsize,
+}
+
+impl AmqpConnector {
+    pub fn try_new(
+        name: impl Into<String>,
+        connect_config: AmqpConnectConfig,
+    ) -> Result<AmqpConnector, Error> {
+        let wrapper = try_new_wrapper(&connect_config)?;
+        let name = name.into();
+
+        health().set_state(name.as_str(), HealthState::Down);
+
+        Ok(AmqpConnector {
+            name,
+            connect_config,
+            wrapper: Mutex::new(wrapper),
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            user_states: std::sync::Mutex::new(HashMap::new()),
+            next_user: AtomicUsize::new(0usize),
+        })
+    }
+
+    ///
+    /// Bounds of the delay between two connection attempts, 500 milliseconds and 30 seconds by
+    /// default.
+    ///
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> AmqpConnector {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn backoff(&self) -> Backoff {
+        Backoff::new(self.initial_backoff, self.max_backoff)
+    }
+
+    ///
+    /// Creates a channel, connecting to the broker first when there is no connection. A failure
+    /// drops the wrapper's connections, so the next call connects again.
+    ///
+    pub async fn try_get_channel(&self) -> Result<Arc<Channel>, Error> {
+        let mut wrapper = self.wrapper.lock().await;
+
+        match wrapper.try_get_channel().await {
+            Ok(channel) => Ok(channel),
+            Err(error) => {
+                *wrapper = try_new_wrapper(&self.connect_config)?;
+
+                Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to get AMQP channel: {}", error),
+                ))
+            }
+        }
+    }
+
+    ///
+    /// Creates a channel, retrying with backoff until the broker is reachable again.
+    ///
+    pub async fn get_channel(&self) -> Arc<Channel> {
+        let mut backoff = self.backoff();
+
+        loop {
+            match self.try_get_channel().await {
+                Ok(channel) => {
+                    info!("'{}' is connected to the broker", self.name);
+
+                    return channel;
+                }
+                Err(error) => {
+                    let delay = backoff.next_delay();
+                    warn!(
+                        "'{}' retrying in {} ms: {}",
+                        self.name,
+                        delay.as_millis(),
+                        error
+                    );
+
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    ///
+    /// Identifier of a new user of the channels, whose state is reported apart from the state
+    /// of the other users.
+    ///
+    pub fn new_user(&self) -> usize {
+        self.next_user.fetch_add(1usize, Ordering::Relaxed)
+    }
+
+    ///
+    /// Reports the connection of the user as up, once what it consumes through a channel is
+    /// declared.
+    ///
+    pub fn report_up(&self, user: usize) {
+        self.report(user, HealthState::Up);
+    }
+
+    ///
+    /// Reports the connection of the user as lost until it is reported as up again.
+    ///
+    pub fn report_lost(&self, user: usize) {
+        self.report(user, HealthState::Reconnecting);
+    }
+
+    fn report(&self, user: usize, state: HealthState) {
+        let mut user_states = match self.user_states.lock() {
+            Ok(user_states) => user_states,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        user_states.insert(user, state);
+        health().set_state(self.name.as_str(), connection_state(&user_states));
+    }
+}
+
+///
+/// State of the connection given the state of each of its users, down until any is up.
+///
+fn connection_state(user_states: &HashMap<usize, HealthState>) -> HealthState {
+    if user_states.is_empty() {
+        HealthState::Down
+    } else if user_states.values().all(|state| *state == HealthState::Up) {
+        HealthState::Up
+    } else {
+        HealthState::Reconnecting
+    }
+}
+
+fn try_new_wrapper(connect_config: &AmqpConnectConfig) -> Result<AmqpWrapper, Error> {
+    match AmqpWrapper::try_new(connect_config.clone()) {
+        Ok(wrapper) => Ok(wrapper),
+        Err(error) => Err(Error::new(
+            ErrorKind::InitializationError,
+            format!("failed to create AMQP wrapper: {}", error),
+        )),
+    }
+}
+
+#[test]
+pub fn report_connection_up_only_while_no_user_lost_it() {
+    let mut user_states: HashMap<usize, HealthState> = HashMap::new();
+
+    assert_eq!(HealthState::Down, connection_state(&user_states));
+
+    user_states.insert(0usize, HealthState::Up);
+    user_states.insert(1usize, HealthState::Reconnecting);
+
+    assert_eq!(HealthState::Reconnecting, connection_state(&user_states));
+
+    // another user recovering does not hide the one still reconnecting
+    user_states.insert(2usize, HealthState::Up);
+
+    assert_eq!(HealthState::Reconnecting, connection_state(&user_states));
+
+    user_states.insert(1usize, HealthState::Up);
+
+    assert_eq!(HealthState::Up, connection_state(&user_states));
+}
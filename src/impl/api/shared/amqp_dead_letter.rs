@@ -0,0 +1,173 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lapin::options::{BasicPublishOptions, QueueBindOptions};
+use lapin::protocol::basic::AMQPProperties;
+use lapin::publisher_confirm::Confirmation;
+use lapin::types::{AMQPValue, FieldTable, LongString, ShortString};
+use lapin::Channel;
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::shared::amqp_exchange::{try_declare_exchange, AmqpExchange};
+use crate::r#impl::api::shared::amqp_queue::AmqpQueue;
+
+///
+/// Header of the dead-lettered messages with the reason they were dead-lettered.
+///
+pub const DEAD_LETTER_ERROR_HEADER: &str = "x-cp-dead-letter-error";
+
+///
+/// Header of the dead-lettered messages with the queue they were consumed from.
+///
+pub const DEAD_LETTER_ORIGINAL_QUEUE_HEADER: &str = "x-cp-dead-letter-original-queue";
+
+///
+/// Header of the dead-lettered messages with the time, in seconds since the Unix epoch, at which
+/// they were dead-lettered.
+///
+pub const DEAD_LETTER_TIMESTAMP_HEADER: &str = "x-cp-dead-letter-timestamp";
+
+///
+/// Exchange and queue the messages which cannot be handled are republished to, along the
+/// routing key binding them.
+///
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AmqpDeadLetter {
+    exchange: AmqpExchange,
+    queue: AmqpQueue,
+    routing_key: String,
+}
+
+impl AmqpDeadLetter {
+    pub fn exchange(&self) -> &AmqpExchange {
+        &self.exchange
+    }
+
+    pub fn queue(&self) -> &AmqpQueue {
+        &self.queue
+    }
+
+    pub fn routing_key(&self) -> &str {
+        &self.routing_key
+    }
+}
+
+///
+/// Declares the dead-letter exchange and queue, and binds them.
+///
+pub(crate) async fn try_declare_dead_letter(
+    channel: &Channel,
+    dead_letter: &AmqpDeadLetter,
+) -> Result<(), Error> {
+    try_declare_exchange(channel, dead_letter.exchange()).await?;
+
+    if let Err(error) = channel
+        .queue_declare(
+            dead_letter.queue().name(),
+            *dead_letter.queue().declare().options(),
+            dead_letter.queue().declare().arguments().clone(),
+        )
+        .await
+    {
+        return Err(Error::new(
+            ErrorKind::ApiError,
+            format!("failed to declare dead-letter queue: {}", error),
+        ));
+    }
+
+    if dead_letter.exchange().name().is_empty() {
+        return Ok(());
+    }
+
+    match channel
+        .queue_bind(
+            dead_letter.queue().name(),
+            dead_letter.exchange().name(),
+            dead_letter.routing_key(),
+            QueueBindOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) => Err(Error::new(
+            ErrorKind::ApiError,
+            format!("failed to bind dead-letter queue: {}", error),
+        )),
+    }
+}
+
+///
+/// Republishes the message to the dead-letter exchange, with headers giving the error, the queue
+/// it was consumed from and the current time.
+///
+pub(crate) async fn try_publish_dead_letter(
+    channel: &Channel,
+    dead_letter: &AmqpDeadLetter,
+    data: &[u8],
+    properties: AMQPProperties,
+    original_queue: &str,
+    error: &Error,
+) -> Result<(), Error> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+
+    let properties = with_dead_letter_headers(properties, original_queue, error, timestamp);
+
+    let confirm = match channel
+        .basic_publish(
+            dead_letter.exchange().name(),
+            dead_letter.routing_key(),
+            BasicPublishOptions::default(),
+            data,
+            properties,
+        )
+        .await
+    {
+        Ok(confirm) => confirm,
+        Err(error) => {
+            return Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to publish dead letter: {}", error),
+            ))
+        }
+    };
+
+    match confirm.await {
+        Ok(Confirmation::Nack(_)) => Err(Error::new(
+            ErrorKind::ApiError,
+            "broker did not accept dead letter",
+        )),
+        Ok(_) => Ok(()),
+        Err(error) => Err(Error::new(
+            ErrorKind::ApiError,
+            format!("failed to confirm dead letter: {}", error),
+        )),
+    }
+}
+
+pub(crate) fn with_dead_letter_headers(
+    properties: AMQPProperties,
+    original_queue: &str,
+    error: &Error,
+    timestamp: u64,
+) -> AMQPProperties {
+    let mut headers = properties.headers().clone().unwrap_or_default();
+
+    headers.insert(
+        ShortString::from(DEAD_LETTER_ERROR_HEADER),
+        AMQPValue::LongString(LongString::from(error.message.as_str())),
+    );
+    headers.insert(
+        ShortString::from(DEAD_LETTER_ORIGINAL_QUEUE_HEADER),
+        AMQPValue::LongString(LongString::from(original_queue)),
+    );
+    headers.insert(
+        ShortString::from(DEAD_LETTER_TIMESTAMP_HEADER),
+        AMQPValue::Timestamp(timestamp),
+    );
+
+    properties.with_headers(headers)
+}
@@ -0,0 +1,326 @@
+use std::sync::Arc;
+
+use lapin::message::BasicGetMessage;
+use lapin::options::{BasicAckOptions, BasicGetOptions, BasicNackOptions, BasicPublishOptions};
+use lapin::protocol::basic::AMQPProperties;
+use lapin::publisher_confirm::Confirmation;
+use lapin::types::{AMQPValue, FieldTable};
+use lapin::Channel;
+use serde_json::Value;
+
+use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::server::input::amqp_input::DELIVERY_ATTEMPTS_HEADER;
+use crate::r#impl::api::shared::amqp_dead_letter::{
+    AmqpDeadLetter, DEAD_LETTER_ERROR_HEADER, DEAD_LETTER_ORIGINAL_QUEUE_HEADER,
+    DEAD_LETTER_TIMESTAMP_HEADER,
+};
+
+///
+/// Message found in the dead-letter queue, along the reason it was dead-lettered.
+///
+#[derive(Debug, PartialEq)]
+pub struct DeadLetteredMessage {
+    pub message_id: Option<String>,
+    pub error: Option<String>,
+    pub original_queue: Option<String>,
+    ///
+    /// Seconds since the Unix epoch.
+    ///
+    pub dead_lettered_at: Option<u64>,
+    ///
+    /// Payload parsed as JSON, or as a string when it is not.
+    ///
+    pub payload: Value,
+}
+
+impl DeadLetteredMessage {
+    pub(crate) fn from_message(properties: &AMQPProperties, data: &[u8]) -> DeadLetteredMessage {
+        let payload = match serde_json::from_slice::<Value>(data) {
+            Ok(payload) => payload,
+            Err(_) => Value::String(String::from_utf8_lossy(data).to_string()),
+        };
+
+        DeadLetteredMessage {
+            message_id: properties
+                .message_id()
+                .as_ref()
+                .map(|message_id| message_id.to_string()),
+            error: long_string_header(properties, DEAD_LETTER_ERROR_HEADER),
+            original_queue: long_string_header(properties, DEAD_LETTER_ORIGINAL_QUEUE_HEADER),
+            dead_lettered_at: properties.headers().as_ref().and_then(|headers| {
+                headers
+                    .inner()
+                    .get(DEAD_LETTER_TIMESTAMP_HEADER)
+                    .and_then(AMQPValue::as_timestamp)
+            }),
+            payload,
+        }
+    }
+}
+
+///
+/// Lists the messages of a dead-letter queue and replays them back to the queue they were
+/// consumed from.
+///
+pub struct AmqpDeadLetterQueue {
+    channel: Arc<Channel>,
+    dead_letter: AmqpDeadLetter,
+}
+
+impl AmqpDeadLetterQueue {
+    pub fn new(channel: Arc<Channel>, dead_letter: AmqpDeadLetter) -> AmqpDeadLetterQueue {
+        AmqpDeadLetterQueue {
+            channel,
+            dead_letter,
+        }
+    }
+
+    ///
+    /// Lists up to `max` messages from the head of the queue. The messages are handed back to
+    /// the queue once all of them are read, so they stay in the same order.
+    ///
+    pub async fn list(&self, max: usize) -> Result<Vec<DeadLetteredMessage>, Error> {
+        let mut messages = Vec::new();
+        let mut delivery_tags = Vec::new();
+
+        while messages.len() < max {
+            let message = match self.try_get().await {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(error) => {
+                    self.requeue(delivery_tags.as_slice()).await;
+                    return Err(error);
+                }
+            };
+
+            messages.push(DeadLetteredMessage::from_message(
+                &message.delivery.properties,
+                message.delivery.data.as_slice(),
+            ));
+            delivery_tags.push(message.delivery.delivery_tag);
+        }
+
+        self.requeue(delivery_tags.as_slice()).await;
+
+        Ok(messages)
+    }
+
+    ///
+    /// Republishes up to `max` messages to the queue they were consumed from, without the
+    /// dead-letter headers nor their count of deliveries, and returns how many were replayed.
+    /// Messages without an original queue are left in the dead-letter queue.
+    ///
+    pub async fn replay(&self, max: usize) -> Result<usize, Error> {
+        let mut replayed = 0usize;
+        let mut skipped_delivery_tags = Vec::new();
+
+        while replayed + skipped_delivery_tags.len() < max {
+            let message = match self.try_get().await {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(error) => {
+                    self.requeue(skipped_delivery_tags.as_slice()).await;
+                    return Err(error);
+                }
+            };
+
+            let delivery = message.delivery;
+            let original_queue =
+                match long_string_header(&delivery.properties, DEAD_LETTER_ORIGINAL_QUEUE_HEADER) {
+                    Some(original_queue) => original_queue,
+                    None => {
+                        skipped_delivery_tags.push(delivery.delivery_tag);
+                        continue;
+                    }
+                };
+
+            let properties = without_dead_letter_headers(delivery.properties.clone());
+
+            if let Err(error) = self
+                .try_publish(
+                    original_queue.as_str(),
+                    delivery.data.as_slice(),
+                    properties,
+                )
+                .await
+            {
+                skipped_delivery_tags.push(delivery.delivery_tag);
+                self.requeue(skipped_delivery_tags.as_slice()).await;
+                return Err(error);
+            }
+
+            if let Err(error) = delivery.ack(BasicAckOptions::default()).await {
+                self.requeue(skipped_delivery_tags.as_slice()).await;
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to acknowledge replayed dead letter: {}", error),
+                ));
+            }
+
+            replayed += 1;
+        }
+
+        self.requeue(skipped_delivery_tags.as_slice()).await;
+
+        Ok(replayed)
+    }
+
+    async fn try_get(&self) -> Result<Option<BasicGetMessage>, Error> {
+        match self
+            .channel
+            .basic_get(self.dead_letter.queue().name(), BasicGetOptions::default())
+            .await
+        {
+            Ok(message) => Ok(message),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("failed to get dead letter: {}", error),
+            )),
+        }
+    }
+
+    async fn try_publish(
+        &self,
+        queue: &str,
+        data: &[u8],
+        properties: AMQPProperties,
+    ) -> Result<(), Error> {
+        let confirm = match self
+            .channel
+            .basic_publish("", queue, BasicPublishOptions::default(), data, properties)
+            .await
+        {
+            Ok(confirm) => confirm,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::ApiError,
+                    format!("failed to replay dead letter to '{}': {}", queue, error),
+                ))
+            }
+        };
+
+        match confirm.await {
+            Ok(Confirmation::Nack(_)) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!("broker did not accept dead letter replayed to '{}'", queue),
+            )),
+            Ok(_) => Ok(()),
+            Err(error) => Err(Error::new(
+                ErrorKind::ApiError,
+                format!(
+                    "failed to confirm dead letter replayed to '{}': {}",
+                    queue, error
+                ),
+            )),
+        }
+    }
+
+    async fn requeue(&self, delivery_tags: &[u64]) {
+        for delivery_tag in delivery_tags {
+            if let Err(error) = self
+                .channel
+                .basic_nack(
+                    *delivery_tag,
+                    BasicNackOptions {
+                        multiple: false,
+                        requeue: true,
+                    },
+                )
+                .await
+            {
+                log::warn!("failed to hand dead letter back to its queue: {}", error);
+            }
+        }
+    }
+}
+
+fn long_string_header(properties: &AMQPProperties, header: &str) -> Option<String> {
+    properties.headers().as_ref().and_then(|headers| {
+        headers
+            .inner()
+            .get(header)
+            .and_then(AMQPValue::as_long_string)
+            .map(|value| value.to_string())
+    })
+}
+
+fn without_dead_letter_headers(properties: AMQPProperties) -> AMQPProperties {
+    let headers = match properties.headers() {
+        Some(headers) => headers,
+        None => return properties,
+    };
+
+    let mut replayed_headers = FieldTable::default();
+
+    for (key, value) in headers.inner() {
+        if key.as_str() == DEAD_LETTER_ERROR_HEADER
+            || key.as_str() == DEAD_LETTER_ORIGINAL_QUEUE_HEADER
+            || key.as_str() == DEAD_LETTER_TIMESTAMP_HEADER
+            || key.as_str() == DELIVERY_ATTEMPTS_HEADER
+        {
+            continue;
+        }
+
+        replayed_headers.insert(key.clone(), value.clone());
+    }
+
+    properties.with_headers(replayed_headers)
+}
+
+#[test]
+pub fn read_failure_reason_of_dead_lettered_message() {
+    use crate::r#impl::api::shared::amqp_dead_letter::with_dead_letter_headers;
+    use lapin::types::ShortString;
+
+    let properties = with_dead_letter_headers(
+        AMQPProperties::default().with_message_id(ShortString::from("message-id")),
+        "organization",
+        &Error::new(ErrorKind::RequestError, "failed to deserialize request"),
+        1_700_000_000u64,
+    );
+
+    let message = DeadLetteredMessage::from_message(&properties, b"not json");
+
+    assert_eq!(
+        DeadLetteredMessage {
+            message_id: Some("message-id".to_string()),
+            error: Some("failed to deserialize request".to_string()),
+            original_queue: Some("organization".to_string()),
+            dead_lettered_at: Some(1_700_000_000u64),
+            payload: Value::String("not json".to_string()),
+        },
+        message
+    );
+}
+
+#[test]
+pub fn strip_dead_letter_headers_on_replay() {
+    use crate::r#impl::api::shared::amqp_dead_letter::with_dead_letter_headers;
+    use lapin::types::{LongString, ShortString};
+
+    let mut headers = FieldTable::default();
+    headers.insert(
+        ShortString::from("x-tenant"),
+        AMQPValue::LongString(LongString::from("cp-organization")),
+    );
+    headers.insert(
+        ShortString::from(DELIVERY_ATTEMPTS_HEADER),
+        AMQPValue::LongUInt(5u32),
+    );
+
+    let properties = with_dead_letter_headers(
+        AMQPProperties::default().with_headers(headers),
+        "organization",
+        &Error::new(ErrorKind::RequestError, "delivery is not an utf8 string"),
+        1_700_000_000u64,
+    );
+
+    let replayed_properties = without_dead_letter_headers(properties);
+    let replayed_headers = replayed_properties
+        .headers()
+        .as_ref()
+        .expect("replayed message has no headers");
+
+    assert_eq!(1usize, replayed_headers.inner().len());
+    assert!(replayed_headers.inner().contains_key("x-tenant"));
+}
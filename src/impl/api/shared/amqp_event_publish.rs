@@ -0,0 +1,32 @@
+use lapin::options::BasicPublishOptions;
+use lapin::protocol::basic::AMQPProperties;
+use serde::{Deserialize, Serialize};
+
+use crate::r#impl::api::shared::amqp_exchange::AmqpExchange;
+
+///
+/// Configuration of an `AmqpEventPublisher`. The exchange is declared once the publisher is
+/// created and the events are published to it with the given options and properties.
+///
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AmqpEventPublish {
+    exchange: AmqpExchange,
+    #[serde(default)]
+    options: BasicPublishOptions,
+    #[serde(default)]
+    properties: AMQPProperties,
+}
+
+impl AmqpEventPublish {
+    pub fn exchange(&self) -> &AmqpExchange {
+        &self.exchange
+    }
+
+    pub fn options(&self) -> &BasicPublishOptions {
+        &self.options
+    }
+
+    pub fn properties(&self) -> &AMQPProperties {
+        &self.properties
+    }
+}
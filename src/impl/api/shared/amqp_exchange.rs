@@ -0,0 +1,62 @@
+use lapin::{Channel, ExchangeKind};
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::{Error, ErrorKind};
+use crate::r#impl::api::shared::amqp_exchange_declare::AmqpExchangeDeclare;
+
+///
+/// Exchange declared by the service, of kind `Direct`, `Topic`, `Fanout`, `Headers` or
+/// `{ "Custom": "..." }`.
+///
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AmqpExchange {
+    name: String,
+    kind: ExchangeKind,
+    declare: AmqpExchangeDeclare,
+}
+
+impl AmqpExchange {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> &ExchangeKind {
+        &self.kind
+    }
+
+    pub fn declare(&self) -> &AmqpExchangeDeclare {
+        &self.declare
+    }
+}
+
+///
+/// Declares the exchange, unless it is the default one, whose name is empty.
+///
+pub(crate) async fn try_declare_exchange(
+    channel: &Channel,
+    exchange: &AmqpExchange,
+) -> Result<(), Error> {
+    if exchange.name().is_empty() {
+        return Ok(());
+    }
+
+    match channel
+        .exchange_declare(
+            exchange.name(),
+            exchange.kind().clone(),
+            *exchange.declare().options(),
+            exchange.declare().arguments().clone(),
+        )
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(error) => Err(Error::new(
+            ErrorKind::ApiError,
+            format!(
+                "failed to declare exchange '{}': {}",
+                exchange.name(),
+                error
+            ),
+        )),
+    }
+}
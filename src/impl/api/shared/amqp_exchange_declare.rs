@@ -0,0 +1,19 @@
+use lapin::options::ExchangeDeclareOptions;
+use lapin::types::FieldTable;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AmqpExchangeDeclare {
+    pub options: ExchangeDeclareOptions,
+    pub arguments: FieldTable,
+}
+
+impl AmqpExchangeDeclare {
+    pub fn options(&self) -> &ExchangeDeclareOptions {
+        &self.options
+    }
+
+    pub fn arguments(&self) -> &FieldTable {
+        &self.arguments
+    }
+}
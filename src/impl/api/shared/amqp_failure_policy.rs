@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::ErrorKind;
+
+///
+/// How the deliveries whose request fails are settled when they are acknowledged after the
+/// reply.
+///
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum AmqpFailurePolicy {
+    ///
+    /// Failed deliveries are rejected with the consumer's reject options.
+    ///
+    #[default]
+    Reject,
+    ///
+    /// Failed deliveries are requeued to be retried.
+    ///
+    Requeue,
+    ///
+    /// Only the deliveries which failed for a reason that may go away are requeued, e.g. a
+    /// timeout or an overloaded service, the rest are rejected.
+    ///
+    RequeueTransient,
+}
+
+impl AmqpFailurePolicy {
+    pub fn requeues(&self, error_kind: ErrorKind) -> bool {
+        match self {
+            AmqpFailurePolicy::Reject => false,
+            AmqpFailurePolicy::Requeue => true,
+            AmqpFailurePolicy::RequeueTransient => error_kind.is_transient(),
+        }
+    }
+}
+
+#[test]
+pub fn requeue_only_transient_failures() {
+    let policy = AmqpFailurePolicy::RequeueTransient;
+
+    assert!(policy.requeues(ErrorKind::TimeoutError));
+    assert!(policy.requeues(ErrorKind::OverloadedError));
+    assert!(!policy.requeues(ErrorKind::ValidationError));
+    assert!(!policy.requeues(ErrorKind::UnknownActionError));
+    assert!(!AmqpFailurePolicy::Reject.requeues(ErrorKind::TimeoutError));
+    assert!(AmqpFailurePolicy::Requeue.requeues(ErrorKind::ValidationError));
+}
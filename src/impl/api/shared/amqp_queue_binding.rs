@@ -0,0 +1,35 @@
+use lapin::options::QueueBindOptions;
+use lapin::types::FieldTable;
+use serde::{Deserialize, Serialize};
+
+///
+/// Binding of the consumed queue to an exchange. The arguments match the headers of the
+/// messages when the exchange is of kind `Headers`.
+///
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AmqpQueueBinding {
+    exchange: String,
+    routing_key: String,
+    #[serde(default)]
+    options: QueueBindOptions,
+    #[serde(default)]
+    arguments: FieldTable,
+}
+
+impl AmqpQueueBinding {
+    pub fn exchange(&self) -> &str {
+        &self.exchange
+    }
+
+    pub fn routing_key(&self) -> &str {
+        &self.routing_key
+    }
+
+    pub fn options(&self) -> &QueueBindOptions {
+        &self.options
+    }
+
+    pub fn arguments(&self) -> &FieldTable {
+        &self.arguments
+    }
+}
@@ -1,7 +1,9 @@
 use lapin::options::{BasicAckOptions, BasicRejectOptions};
 use serde::{Deserialize, Serialize};
 
+use crate::r#impl::api::shared::amqp_acknowledge_mode::AmqpAcknowledgeMode;
 use crate::r#impl::api::shared::amqp_consume::AmqpConsume;
+use crate::r#impl::api::shared::amqp_failure_policy::AmqpFailurePolicy;
 use crate::r#impl::api::shared::amqp_qos::AmqpQos;
 use crate::r#impl::api::shared::amqp_queue::AmqpQueue;
 
@@ -12,6 +14,17 @@ pub struct AmqpQueueConsumer {
     consume: AmqpConsume,
     acknowledge: BasicAckOptions,
     reject: BasicRejectOptions,
+    #[serde(default)]
+    acknowledge_mode: AmqpAcknowledgeMode,
+    #[serde(default)]
+    failure_policy: AmqpFailurePolicy,
+    ///
+    /// Number of times a delivery is handled before it is dead-lettered instead. Failed
+    /// deliveries are republished with their count in the `x-cp-delivery-attempts` header, to
+    /// which the `x-delivery-count` header or, lacking it, the redelivered flag is added.
+    ///
+    #[serde(default)]
+    max_deliveries: Option<u32>,
 }
 
 impl AmqpQueueConsumer {
@@ -34,4 +47,16 @@ impl AmqpQueueConsumer {
     pub fn reject(&self) -> &BasicRejectOptions {
         &self.reject
     }
+
+    pub fn acknowledge_mode(&self) -> AmqpAcknowledgeMode {
+        self.acknowledge_mode
+    }
+
+    pub fn failure_policy(&self) -> AmqpFailurePolicy {
+        self.failure_policy
+    }
+
+    pub fn max_deliveries(&self) -> Option<u32> {
+        self.max_deliveries
+    }
 }
@@ -8,6 +8,12 @@ pub struct AmqpQueueRpcPublisher {
     queue_name: String,
     publish: AmqpPublish,
     response: AmqpQueueConsumer,
+    ///
+    /// Replies are consumed through RabbitMQ's direct reply-to, `amq.rabbitmq.reply-to`, instead
+    /// of the response queue, which is then neither declared nor consumed.
+    ///
+    #[serde(default)]
+    direct_reply_to: bool,
 }
 
 impl AmqpQueueRpcPublisher {
@@ -22,4 +28,8 @@ impl AmqpQueueRpcPublisher {
     pub fn response(&self) -> &AmqpQueueConsumer {
         &self.response
     }
+
+    pub fn direct_reply_to(&self) -> bool {
+        self.direct_reply_to
+    }
 }
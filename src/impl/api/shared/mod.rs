@@ -1,8 +1,18 @@
+pub mod amqp_acknowledge_mode;
 pub mod amqp_api_entry;
+pub mod amqp_connector;
 pub mod amqp_consume;
+pub mod amqp_dead_letter;
+pub mod amqp_dead_letter_queue;
+pub mod amqp_event_publish;
+pub mod amqp_exchange;
+pub mod amqp_exchange_declare;
+pub mod amqp_failure_policy;
 pub mod amqp_publish;
 pub mod amqp_qos;
 pub mod amqp_queue;
+pub mod amqp_queue_binding;
 pub mod amqp_queue_consumer;
 pub mod amqp_queue_declare;
 pub mod amqp_queue_rpc_publisher;
+pub mod unix_socket_frame;
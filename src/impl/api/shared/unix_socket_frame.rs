@@ -0,0 +1,45 @@
+use bytes::Bytes;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::shared::request::Request;
+use crate::core::error::{Error, ErrorKind};
+
+///
+/// Frame sent by a `UnixSocketInputConsumer`, the reply to the request carries the same
+/// correlation id.
+///
+#[derive(Deserialize, Serialize)]
+pub struct UnixSocketRequestFrame {
+    pub correlation_id: String,
+    pub request: Request,
+}
+
+///
+/// Frame sent by a `UnixSocketInput` with the reply of the request of the same correlation id.
+///
+#[derive(Deserialize, Serialize)]
+pub struct UnixSocketReplyFrame {
+    pub correlation_id: String,
+    pub response: Value,
+}
+
+pub fn encode_frame<FrameType: Serialize>(frame: &FrameType) -> Result<Bytes, Error> {
+    match serde_json::to_vec(frame) {
+        Ok(bytes) => Ok(Bytes::from(bytes)),
+        Err(error) => Err(Error::new(
+            ErrorKind::ApiError,
+            format!("failed to serialize frame: {}", error),
+        )),
+    }
+}
+
+pub fn decode_frame<FrameType: DeserializeOwned>(bytes: &[u8]) -> Result<FrameType, Error> {
+    match serde_json::from_slice::<FrameType>(bytes) {
+        Ok(frame) => Ok(frame),
+        Err(error) => Err(Error::new(
+            ErrorKind::RequestError,
+            format!("failed to deserialize frame: {}", error),
+        )),
+    }
+}
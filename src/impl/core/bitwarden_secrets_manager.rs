@@ -5,16 +5,18 @@ use serde::Deserialize;
 use crate::core::secrets::secrets_manager::SecretsManager;
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
 struct BitwardenSecret {
     pub object: String,
     pub id: String,
-    pub organizationId: String,
-    pub projectId: String,
+    pub organization_id: String,
+    pub project_id: String,
     pub key: String,
     pub value: String,
     pub note: String,
-    pub creationDate: String,
-    pub revisionDate: String,
+    pub creation_date: String,
+    pub revision_date: String,
 }
 
 pub struct BitwardenSecretsManager {
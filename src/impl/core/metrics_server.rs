@@ -0,0 +1,224 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{info, warn};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::core::error::{Error, ErrorKind};
+use crate::core::health::{health, Health};
+use crate::core::metrics::metrics;
+
+pub const METRICS_PATH: &str = "/metrics";
+
+pub const HEALTH_PATH: &str = "/health";
+
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+///
+/// HTTP server which exposes the metrics in the Prometheus text format at `/metrics`, and the
+/// health of the service at `/health`, which answers `503 Service Unavailable` while any
+/// component is not up.
+///
+pub struct MetricsServer {
+    local_address: SocketAddr,
+    handle: JoinHandle<()>,
+}
+
+impl MetricsServer {
+    ///
+    /// Binds the given address and serves the metrics until the cancellation token is cancelled.
+    ///
+    pub fn try_start(
+        address: SocketAddr,
+        cancellation_token: CancellationToken,
+    ) -> Result<MetricsServer, Error> {
+        MetricsServer::try_start_with_health(address, health(), cancellation_token)
+    }
+
+    ///
+    /// Like `try_start`, reporting the given health instead of the service's one.
+    ///
+    pub fn try_start_with_health(
+        address: SocketAddr,
+        health: &'static Health,
+        cancellation_token: CancellationToken,
+    ) -> Result<MetricsServer, Error> {
+        let incoming = match AddrIncoming::bind(&address) {
+            Ok(incoming) => incoming,
+            Err(error) => {
+                return Err(Error::new(
+                    ErrorKind::InitializationError,
+                    format!("failed to bind metrics server to '{}': {}", address, error),
+                ))
+            }
+        };
+        let local_address = incoming.local_addr();
+
+        let make_service = make_service_fn(move |_| async move {
+            Ok::<_, Infallible>(service_fn(move |request| handle_request(request, health)))
+        });
+
+        let server = Server::builder(incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(async move { cancellation_token.cancelled().await });
+
+        let handle = tokio::spawn(async move {
+            if let Err(error) = server.await {
+                warn!("metrics server failed: {}", error);
+            }
+        });
+
+        info!("metrics server listening on '{}'", local_address);
+
+        Ok(MetricsServer {
+            local_address,
+            handle,
+        })
+    }
+
+    pub fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+
+    pub fn handle(self) -> JoinHandle<()> {
+        self.handle
+    }
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    health: &'static Health,
+) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::GET {
+        return Ok(build_response(
+            StatusCode::NOT_FOUND,
+            "not found".to_string(),
+            PROMETHEUS_CONTENT_TYPE,
+        ));
+    }
+
+    match request.uri().path() {
+        METRICS_PATH => match metrics().encode() {
+            Ok(text) => Ok(build_response(
+                StatusCode::OK,
+                text,
+                PROMETHEUS_CONTENT_TYPE,
+            )),
+            Err(error) => Ok(build_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error.to_string(),
+                PROMETHEUS_CONTENT_TYPE,
+            )),
+        },
+        HEALTH_PATH => {
+            let status = if health.is_healthy() {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+
+            Ok(build_response(
+                status,
+                health.report().to_string(),
+                JSON_CONTENT_TYPE,
+            ))
+        }
+        _ => Ok(build_response(
+            StatusCode::NOT_FOUND,
+            "not found".to_string(),
+            PROMETHEUS_CONTENT_TYPE,
+        )),
+    }
+}
+
+fn build_response(status: StatusCode, body: String, content_type: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+
+    if let Ok(content_type) = content_type.parse() {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+
+    response
+}
+
+#[tokio::test]
+pub async fn serve_metrics_in_prometheus_format() {
+    let cancellation_token = CancellationToken::new();
+    let server = MetricsServer::try_start(
+        "127.0.0.1:0".parse().expect("invalid address"),
+        cancellation_token.clone(),
+    )
+    .expect("failed to start metrics server");
+
+    // the channel label is only used by this test, so the metrics recorded by the tests running
+    // in parallel do not change it
+    metrics().set_queue_depth("metrics_server_test", 3usize);
+
+    let body = reqwest::get(format!("http://{}{}", server.local_address(), METRICS_PATH))
+        .await
+        .expect("failed to request metrics")
+        .text()
+        .await
+        .expect("failed to read metrics");
+
+    assert!(body.contains("cp_microservice_queue_depth{channel=\"metrics_server_test\"} 3"));
+
+    cancellation_token.cancel();
+    server
+        .handle()
+        .await
+        .expect("failed to stop metrics server");
+}
+
+#[tokio::test]
+pub async fn serve_unavailable_health_while_component_is_reconnecting() {
+    use crate::core::health::HealthState;
+
+    // the service's health is shared with the tests running in parallel
+    let health: &'static Health = Box::leak(Box::new(Health::new()));
+
+    let cancellation_token = CancellationToken::new();
+    let server = MetricsServer::try_start_with_health(
+        "127.0.0.1:0".parse().expect("invalid address"),
+        health,
+        cancellation_token.clone(),
+    )
+    .expect("failed to start metrics server");
+
+    let health_url = format!("http://{}{}", server.local_address(), HEALTH_PATH);
+
+    health.set_state("metrics_server_test", HealthState::Reconnecting);
+
+    let response = reqwest::get(health_url.as_str())
+        .await
+        .expect("failed to request health");
+
+    assert_eq!(503u16, response.status().as_u16());
+
+    let report: serde_json::Value = response.json().await.expect("failed to read health");
+
+    assert_eq!("down", report["status"]);
+    assert_eq!("reconnecting", report["components"]["metrics_server_test"]);
+
+    health.set_state("metrics_server_test", HealthState::Up);
+
+    let response = reqwest::get(health_url.as_str())
+        .await
+        .expect("failed to request health");
+
+    assert_eq!(200u16, response.status().as_u16());
+
+    cancellation_token.cancel();
+    server
+        .handle()
+        .await
+        .expect("failed to stop metrics server");
+}
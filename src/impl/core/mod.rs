@@ -1 +1,3 @@
 pub mod bitwarden_secrets_manager;
+pub mod metrics_server;
+pub mod tracing_exporter;
@@ -0,0 +1,102 @@
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use crate::core::error::{Error, ErrorKind};
+
+const DEFAULT_TRACING_FILTER: &str = "info";
+
+///
+/// Destination of the spans recorded by the API, logic and storage dispatchers.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum TracingExporter {
+    ///
+    /// Writes the events and the closed spans, along their duration, to stdout.
+    ///
+    Stdout,
+    ///
+    /// Exports the spans through OTLP over gRPC, besides writing the events to stdout. Requires
+    /// the `otlp` feature.
+    ///
+    #[cfg(feature = "otlp")]
+    Otlp {
+        endpoint: String,
+        service_name: String,
+    },
+}
+
+///
+/// Installs the global tracing subscriber for the given exporter. The records of the `log`
+/// crate are forwarded to it, and the `RUST_LOG` environment variable filters what is recorded.
+///
+pub fn try_init_tracing(exporter: TracingExporter) -> Result<(), Error> {
+    let filter = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => EnvFilter::new(DEFAULT_TRACING_FILTER),
+    };
+
+    let result = match exporter {
+        TracingExporter::Stdout => tracing_subscriber::registry()
+            .with(filter)
+            .with(tracing_subscriber::fmt::layer().with_span_events(FmtSpan::CLOSE))
+            .try_init(),
+        #[cfg(feature = "otlp")]
+        TracingExporter::Otlp {
+            endpoint,
+            service_name,
+        } => {
+            let tracer = try_create_otlp_tracer(endpoint, service_name)?;
+
+            tracing_subscriber::registry()
+                .with(filter)
+                .with(tracing_subscriber::fmt::layer())
+                .with(tracing_opentelemetry::layer().with_tracer(tracer))
+                .try_init()
+        }
+    };
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(error) => Err(Error::new(
+            ErrorKind::InitializationError,
+            format!("failed to initialize tracing: {}", error),
+        )),
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn try_create_otlp_tracer(
+    endpoint: String,
+    service_name: String,
+) -> Result<opentelemetry_sdk::trace::Tracer, Error> {
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(error) => {
+            return Err(Error::new(
+                ErrorKind::InitializationError,
+                format!("failed to create OTLP span exporter: {}", error),
+            ))
+        }
+    };
+
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(opentelemetry_sdk::Resource::new(vec![
+            opentelemetry::KeyValue::new("service.name", service_name.clone()),
+        ]))
+        .build();
+
+    let tracer = provider.tracer(service_name);
+    opentelemetry::global::set_tracer_provider(provider);
+
+    Ok(tracer)
+}
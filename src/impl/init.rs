@@ -1,26 +1,104 @@
 use std::mem::Discriminant;
+use std::net::SocketAddr;
+use std::time::Duration;
 use std::{collections::HashMap, sync::Arc};
 
 use async_channel::Sender;
 use futures_util::future::join_all;
-use multiple_connections_lapin_wrapper::{
-    amqp_wrapper::AmqpWrapper, config::amqp_connect_config::AmqpConnectConfig,
-};
+use log::{info, warn};
+use multiple_connections_lapin_wrapper::config::amqp_connect_config::AmqpConnectConfig;
 use tokio_util::sync::CancellationToken;
 
+use crate::api::server::dispatch::{
+    ShutdownReport, DEFAULT_DRAIN_TIMEOUT, DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+};
 use crate::api::server::input::action::Action;
+use crate::api::shared::response::ResponseFormat;
+use crate::core::trace_context::SpanExtractor;
 use crate::r#impl::api::shared::amqp_api_entry::AmqpApiEntry;
+use crate::r#impl::api::shared::amqp_connector::AmqpConnector;
+use crate::r#impl::core::metrics_server::MetricsServer;
+use crate::r#impl::core::tracing_exporter::{try_init_tracing, TracingExporter};
 use crate::r#impl::process_signals::listen_to_process_signals;
 use crate::{
     api::server::input::input_plugin::InputPlugin,
     r#impl::api::server::input::amqp_input::AmqpInput,
 };
 
+///
+/// Name under which the state of the broker connection is reported to the health endpoint.
+///
+pub const AMQP_HEALTH_COMPONENT: &str = "amqp";
+
+///
+/// Configuration of the API layer. Built through `new`, whereas the optional settings are set
+/// through the `with_` methods, so new settings do not break the existing initializations.
+///
 pub struct ApiInitializationPackage<LogicRequestType: 'static + Send + Sync + std::fmt::Debug> {
     pub amqp_connection_config: AmqpConnectConfig,
     pub amqp_api: Vec<AmqpApiEntry>,
     pub actions: HashMap<String, Action<LogicRequestType>>,
     pub plugins: Vec<Arc<dyn InputPlugin + Send + Sync>>,
+    max_in_flight_requests_per_input: usize,
+    drain_timeout: Duration,
+    response_format: ResponseFormat,
+    metrics_address: Option<SocketAddr>,
+    tracing_exporter: Option<TracingExporter>,
+}
+
+impl<LogicRequestType: 'static + Send + Sync + std::fmt::Debug>
+    ApiInitializationPackage<LogicRequestType>
+{
+    pub fn new(
+        amqp_connection_config: AmqpConnectConfig,
+        amqp_api: Vec<AmqpApiEntry>,
+        actions: HashMap<String, Action<LogicRequestType>>,
+        plugins: Vec<Arc<dyn InputPlugin + Send + Sync>>,
+    ) -> ApiInitializationPackage<LogicRequestType> {
+        ApiInitializationPackage {
+            amqp_connection_config,
+            amqp_api,
+            actions,
+            plugins,
+            max_in_flight_requests_per_input: DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            response_format: ResponseFormat::default(),
+            metrics_address: None,
+            tracing_exporter: None,
+        }
+    }
+
+    pub fn with_max_in_flight_requests_per_input(mut self, max_in_flight_requests: usize) -> Self {
+        self.max_in_flight_requests_per_input = max_in_flight_requests;
+        self
+    }
+
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    pub fn with_response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response_format = response_format;
+        self
+    }
+
+    ///
+    /// Address on which the metrics are served in the Prometheus text format, no metrics
+    /// endpoint is started otherwise.
+    ///
+    pub fn with_metrics_address(mut self, metrics_address: SocketAddr) -> Self {
+        self.metrics_address = Some(metrics_address);
+        self
+    }
+
+    ///
+    /// Exporter of the spans of each request, no tracing subscriber is installed otherwise.
+    ///
+    pub fn with_tracing_exporter(mut self, tracing_exporter: TracingExporter) -> Self {
+        self.tracing_exporter = Some(tracing_exporter);
+        self
+    }
 }
 
 pub struct LogicInitializationPackage<
@@ -32,6 +110,35 @@ pub struct LogicInitializationPackage<
         crate::logic::executor::Executor<LogicRequestType, StorageRequestType>,
     >,
     pub storage_request_sender: Sender<StorageRequestType>,
+    span_extractor: Option<SpanExtractor<LogicRequestType>>,
+}
+
+impl<
+        LogicRequestType: 'static + Send + Sync + std::fmt::Debug,
+        StorageRequestType: 'static + Send + Sync,
+    > LogicInitializationPackage<LogicRequestType, StorageRequestType>
+{
+    pub fn new(
+        executors: HashMap<
+            Discriminant<LogicRequestType>,
+            crate::logic::executor::Executor<LogicRequestType, StorageRequestType>,
+        >,
+        storage_request_sender: Sender<StorageRequestType>,
+    ) -> LogicInitializationPackage<LogicRequestType, StorageRequestType> {
+        LogicInitializationPackage {
+            executors,
+            storage_request_sender,
+            span_extractor: None,
+        }
+    }
+
+    ///
+    /// Extracts from each logic request the span of the API request which created it.
+    ///
+    pub fn with_span_extractor(mut self, span_extractor: SpanExtractor<LogicRequestType>) -> Self {
+        self.span_extractor = Some(span_extractor);
+        self
+    }
 }
 
 pub async fn try_initialize_microservice<
@@ -43,21 +150,41 @@ pub async fn try_initialize_microservice<
 ) -> Result<(), std::io::Error> {
     let cancellation_token = CancellationToken::new();
 
+    if let Some(tracing_exporter) = api_initialization_package.tracing_exporter {
+        if let Err(error) = try_init_tracing(tracing_exporter) {
+            return Err(std::io::Error::other(format!(
+                "failed to initialize tracing: {}",
+                error
+            )));
+        }
+    }
+
     listen_to_process_signals(cancellation_token.clone());
 
-    let amqp_wrapper = match AmqpWrapper::try_new(api_initialization_package.amqp_connection_config)
-    {
-        Ok(amqp_wrapper) => amqp_wrapper,
+    if let Some(metrics_address) = api_initialization_package.metrics_address {
+        if let Err(error) = MetricsServer::try_start(metrics_address, cancellation_token.clone()) {
+            return Err(std::io::Error::other(format!(
+                "failed to start metrics server: {}",
+                error
+            )));
+        }
+    }
+
+    let amqp_connector = match AmqpConnector::try_new(
+        AMQP_HEALTH_COMPONENT,
+        api_initialization_package.amqp_connection_config,
+    ) {
+        Ok(amqp_connector) => Arc::new(amqp_connector),
         Err(error) => {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("failed to create AMQP wrapper: {}", error),
-            ))
+            return Err(std::io::Error::other(format!(
+                "failed to create AMQP connector: {}",
+                error
+            )))
         }
     };
 
     let amqp_inputs =
-        generate_inputs_from_api(amqp_wrapper, api_initialization_package.amqp_api).await?;
+        generate_inputs_from_api(amqp_connector, api_initialization_package.amqp_api).await?;
 
     let (logic_request_sender, logic_request_receiver) =
         async_channel::bounded::<LogicRequestType>(1024usize);
@@ -68,19 +195,37 @@ pub async fn try_initialize_microservice<
             api_initialization_package.actions,
             logic_request_sender,
             api_initialization_package.plugins,
-        );
+        )
+        .with_max_in_flight_requests(api_initialization_package.max_in_flight_requests_per_input)
+        .with_drain_timeout(api_initialization_package.drain_timeout)
+        .with_response_format(api_initialization_package.response_format);
 
     let api_cancellation_token = cancellation_token.clone();
     tokio::spawn(async move {
         // when handles have finished, the program will exit since an exit signal is sent to the process
         let handles = api_dispatch.run(api_cancellation_token).await;
 
-        join_all(handles).await;
+        let mut shutdown_report = ShutdownReport::default();
+
+        for result in join_all(handles).await {
+            match result {
+                Ok(input_shutdown_report) => {
+                    shutdown_report = shutdown_report.merge(input_shutdown_report)
+                }
+                Err(error) => warn!("api dispatch input failed: {}", error),
+            }
+        }
+
+        info!(
+            "api dispatch stopped, drained requests: {}, abandoned requests: {}",
+            shutdown_report.drained(),
+            shutdown_report.abandoned()
+        );
 
         std::process::exit(0);
     });
 
-    let logic_dispatch: crate::logic::dispatch::Dispatch<LogicRequestType, StorageRequestType> =
+    let mut logic_dispatch: crate::logic::dispatch::Dispatch<LogicRequestType, StorageRequestType> =
         crate::logic::dispatch::Dispatch::new(
             logic_request_receiver,
             logic_initialization_package.executors,
@@ -88,38 +233,32 @@ pub async fn try_initialize_microservice<
             cancellation_token,
         );
 
+    if let Some(span_extractor) = logic_initialization_package.span_extractor {
+        logic_dispatch = logic_dispatch.with_span_extractor(span_extractor);
+    }
+
     tokio::spawn(logic_dispatch.run());
 
     Ok(())
 }
 
 async fn generate_inputs_from_api(
-    mut amqp_wrapper: AmqpWrapper,
+    amqp_connector: Arc<AmqpConnector>,
     amqp_api: Vec<AmqpApiEntry>,
 ) -> Result<Vec<AmqpInput>, std::io::Error> {
     let mut inputs: Vec<AmqpInput> = Vec::new();
 
     for amqp_api_entry in amqp_api {
-        let channel = match amqp_wrapper.try_get_channel().await {
-            Ok(channel) => channel,
-            Err(error) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("failed to get AMQP channel: {}", &error),
-                ))
-            }
-        };
-
-        let amqp_input = match AmqpInput::try_new(channel, amqp_api_entry.amqp_queue_consumer).await
-        {
-            Ok(amqp_input) => amqp_input,
-            Err(error) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("failed to create AMQP input: {}", &error),
-                ))
-            }
-        };
+        let amqp_input =
+            match AmqpInput::try_from_connector(amqp_connector.clone(), amqp_api_entry).await {
+                Ok(amqp_input) => amqp_input,
+                Err(error) => {
+                    return Err(std::io::Error::other(format!(
+                        "failed to create AMQP input: {}",
+                        &error
+                    )))
+                }
+            };
 
         inputs.push(amqp_input);
     }
@@ -2,15 +2,23 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::mem;
 use std::mem::Discriminant;
+#[cfg(test)]
 use std::sync::Arc;
+#[cfg(test)]
 use std::time::Duration;
+use std::time::Instant;
 
 use async_channel::{Receiver, Sender};
 use log::info;
+#[cfg(test)]
 use tokio::time::timeout;
 use tokio_util::sync::CancellationToken;
+use tracing::{info_span, Instrument};
 
+#[cfg(test)]
 use crate::core::error::Error;
+use crate::core::metrics::{metrics, variant_name};
+use crate::core::trace_context::SpanExtractor;
 use crate::logic::executor::Executor;
 
 pub struct Dispatch<LogicRequestType: Debug, StorageRequestType> {
@@ -19,6 +27,7 @@ pub struct Dispatch<LogicRequestType: Debug, StorageRequestType> {
         HashMap<Discriminant<LogicRequestType>, Executor<LogicRequestType, StorageRequestType>>,
     storage_request_sender: Sender<StorageRequestType>,
     cancellation_token: CancellationToken,
+    span_extractor: Option<SpanExtractor<LogicRequestType>>,
 }
 
 impl<LogicRequestType: Debug, StorageRequestType> Dispatch<LogicRequestType, StorageRequestType> {
@@ -36,10 +45,25 @@ impl<LogicRequestType: Debug, StorageRequestType> Dispatch<LogicRequestType, Sto
             executors,
             storage_request_sender,
             cancellation_token,
+            span_extractor: None,
         }
     }
 
+    ///
+    /// Extracts from each logic request the span of the API request which created it, so the
+    /// logic request's span continues its trace.
+    ///
+    pub fn with_span_extractor(
+        mut self,
+        span_extractor: SpanExtractor<LogicRequestType>,
+    ) -> Dispatch<LogicRequestType, StorageRequestType> {
+        self.span_extractor = Some(span_extractor);
+        self
+    }
+
     pub async fn run(self) {
+        let mut variant_names: HashMap<Discriminant<LogicRequestType>, String> = HashMap::new();
+
         loop {
             if self.cancellation_token.is_cancelled() && self.logic_request_receiver.is_empty() {
                 info!(
@@ -57,7 +81,10 @@ impl<LogicRequestType: Debug, StorageRequestType> Dispatch<LogicRequestType, Sto
                 }
             };
 
-            let executor = match self.executors.get(&mem::discriminant(&logic_request)) {
+            metrics().set_queue_depth("logic_requests", self.logic_request_receiver.len());
+
+            let discriminant = mem::discriminant(&logic_request);
+            let executor = match self.executors.get(&discriminant) {
                 Some(executor) => executor,
                 None => {
                     info!(
@@ -68,7 +95,34 @@ impl<LogicRequestType: Debug, StorageRequestType> Dispatch<LogicRequestType, Sto
                 }
             };
 
-            if let Err(error) = executor(logic_request, self.storage_request_sender.clone()).await {
+            let variant_name = variant_names
+                .entry(discriminant)
+                .or_insert_with(|| variant_name(&logic_request))
+                .clone();
+            let span = match self
+                .span_extractor
+                .as_ref()
+                .and_then(|span_extractor| span_extractor(&logic_request))
+            {
+                Some(parent) => {
+                    info_span!(parent: &parent, "logic_request", request = %variant_name)
+                }
+                None => info_span!("logic_request", request = %variant_name),
+            };
+            let started_at = Instant::now();
+
+            let result = executor(logic_request, self.storage_request_sender.clone())
+                .instrument(span)
+                .await;
+
+            metrics().record_executor(
+                "logic",
+                &variant_name,
+                result.is_err(),
+                started_at.elapsed(),
+            );
+
+            if let Err(error) = result {
                 info!("logic executor returned error: {}", &error);
             }
         }
@@ -90,6 +144,7 @@ async fn dummy_executor(
     Ok(())
 }
 
+#[cfg(test)]
 const TEST_STORAGE_REQUEST_VALUE: &str = "ok";
 
 #[derive(Debug)]
@@ -140,8 +195,71 @@ pub async fn run_expected_executors() {
     .expect("timeout waiting for storage request")
     .expect("failed to receive storage request");
 
-    match request {
-        StorageRequest::DummyElement(value) => assert_eq!(TEST_STORAGE_REQUEST_VALUE, value),
-        _ => panic!("unexpected storage request value received"),
-    }
+    let StorageRequest::DummyElement(value) = request;
+
+    assert_eq!(TEST_STORAGE_REQUEST_VALUE, value);
+}
+
+#[tokio::test]
+pub async fn continue_trace_of_extracted_span() {
+    use tracing::Span;
+    use tracing_subscriber::registry::LookupSpan;
+
+    let _subscriber_guard = tracing::subscriber::set_default(tracing_subscriber::registry());
+    let api_request_span = tracing::info_span!("api_request");
+
+    let (parent_sender, mut parent_receiver) = tokio::sync::mpsc::channel::<Option<String>>(1usize);
+    let exec: Executor<LogicRequest, StorageRequest> =
+        Arc::new(move |_logic_request, _storage_request_sender| {
+            let parent_sender = parent_sender.clone();
+
+            Box::pin(async move {
+                let parent_name = Span::current().with_subscriber(|(id, dispatch)| {
+                    dispatch
+                        .downcast_ref::<tracing_subscriber::Registry>()
+                        .and_then(|registry| registry.span(id))
+                        .and_then(|span| span.parent())
+                        .map(|parent| parent.name().to_string())
+                });
+
+                parent_sender
+                    .send(parent_name.flatten())
+                    .await
+                    .expect("failed to send parent name");
+
+                Ok(())
+            })
+        });
+
+    let executors: HashMap<Discriminant<LogicRequest>, Executor<LogicRequest, StorageRequest>> =
+        HashMap::from([(
+            mem::discriminant(&LogicRequest::DummyElement("".to_string())),
+            exec,
+        )]);
+
+    let (sender, receiver) = async_channel::unbounded::<LogicRequest>();
+    let (storage_request_sender, _) = async_channel::unbounded::<StorageRequest>();
+
+    let extracted_span = api_request_span.clone();
+    let dispatch: Dispatch<LogicRequest, StorageRequest> = Dispatch::new(
+        receiver,
+        executors,
+        storage_request_sender,
+        CancellationToken::new(),
+    )
+    .with_span_extractor(Arc::new(move |_| Some(extracted_span.clone())));
+
+    tokio::spawn(dispatch.run());
+
+    sender
+        .send(LogicRequest::DummyElement("random".to_string()))
+        .await
+        .expect("failed to send logic request");
+
+    let parent_name = timeout(Duration::from_millis(200u64), parent_receiver.recv())
+        .await
+        .expect("timeout waiting for executor")
+        .expect("failed to receive parent name");
+
+    assert_eq!(Some("api_request".to_string()), parent_name);
 }
@@ -17,6 +17,10 @@ pub type Executor<LogicRequestType, StorageRequestType> = Arc<
         + Sync,
 >;
 
+#[tracing::instrument(
+    name = "send_storage_request",
+    skip(storage_request, sender, api_replier)
+)]
 pub async fn timeout_send_storage_request<StorageRequestType, OkResultType>(
     timeout_after_milliseconds: u64,
     storage_request: StorageRequestType,
@@ -37,7 +41,7 @@ pub async fn timeout_send_storage_request<StorageRequestType, OkResultType>(
                     format!("failed to send storage request: {}", &error),
                 );
 
-                if let Err(_) = api_replier.send(Err(error.clone())) {
+                if api_replier.send(Err(error.clone())).is_err() {
                     log::warn!("failed to reply to api with an error");
                 }
 
@@ -50,7 +54,7 @@ pub async fn timeout_send_storage_request<StorageRequestType, OkResultType>(
                 format!("timed out sending storage request: {}", &error),
             );
 
-            if let Err(_) = api_replier.send(Err(error.clone())) {
+            if api_replier.send(Err(error.clone())).is_err() {
                 log::warn!("failed to reply to api with an error");
             }
 
@@ -61,6 +65,7 @@ pub async fn timeout_send_storage_request<StorageRequestType, OkResultType>(
     Ok(api_replier)
 }
 
+#[tracing::instrument(name = "receive_storage_response", skip(storage_receiver, api_replier))]
 pub async fn timeout_receive_storage_response<StorageOkResultType, LogicOkResultType>(
     timeout_after_milliseconds: u64,
     storage_receiver: tokio::sync::oneshot::Receiver<Result<StorageOkResultType, Error>>,
@@ -87,7 +92,7 @@ pub async fn timeout_receive_storage_response<StorageOkResultType, LogicOkResult
                         format!("storage failed to handle request: {}", &error),
                     );
 
-                    if let Err(_) = api_replier.send(Err(error.clone())) {
+                    if api_replier.send(Err(error.clone())).is_err() {
                         log::warn!("failed to reply to api with an error");
                     }
 
@@ -100,7 +105,7 @@ pub async fn timeout_receive_storage_response<StorageOkResultType, LogicOkResult
                     format!("failed to receive response from storage: {}", &error),
                 );
 
-                if let Err(_) = api_replier.send(Err(error.clone())) {
+                if api_replier.send(Err(error.clone())).is_err() {
                     log::warn!("failed to reply to api with an error")
                 }
 
@@ -113,7 +118,7 @@ pub async fn timeout_receive_storage_response<StorageOkResultType, LogicOkResult
                 format!("timed out receiving response from storage: {}", &error),
             );
 
-            if let Err(_) = api_replier.send(Err(error.clone())) {
+            if api_replier.send(Err(error.clone())).is_err() {
                 log::warn!("failed to reply to api with an error");
             }
 
@@ -2,18 +2,23 @@ use std::{
     collections::HashMap,
     fmt::Debug,
     mem::{self, Discriminant},
+    time::Instant,
 };
 
 use async_channel::Receiver;
 use log::info;
 use tokio_util::sync::CancellationToken;
+use tracing::{info_span, Instrument};
 
+use crate::core::metrics::{metrics, variant_name};
+use crate::core::trace_context::SpanExtractor;
 use crate::storage::executor::Executor;
 
 pub struct Dispatch<StorageRequestType: Debug> {
     storage_request_receiver: Receiver<StorageRequestType>,
     executors: HashMap<Discriminant<StorageRequestType>, Executor<StorageRequestType>>,
     cancellation_token: CancellationToken,
+    span_extractor: Option<SpanExtractor<StorageRequestType>>,
 }
 
 impl<StorageRequestType: Debug> Dispatch<StorageRequestType> {
@@ -26,10 +31,25 @@ impl<StorageRequestType: Debug> Dispatch<StorageRequestType> {
             storage_request_receiver,
             executors,
             cancellation_token,
+            span_extractor: None,
         }
     }
 
+    ///
+    /// Extracts from each storage request the span of the logic request which created it, so
+    /// the storage request's span continues its trace.
+    ///
+    pub fn with_span_extractor(
+        mut self,
+        span_extractor: SpanExtractor<StorageRequestType>,
+    ) -> Self {
+        self.span_extractor = Some(span_extractor);
+        self
+    }
+
     pub async fn run(self) {
+        let mut variant_names: HashMap<Discriminant<StorageRequestType>, String> = HashMap::new();
+
         loop {
             if self.cancellation_token.is_cancelled() && self.storage_request_receiver.is_empty() {
                 info!("cancellation token is cancelled and storage request receiver is empty, storage dispatch is stopping");
@@ -44,7 +64,10 @@ impl<StorageRequestType: Debug> Dispatch<StorageRequestType> {
                 }
             };
 
-            let executor = match self.executors.get(&mem::discriminant(&storage_request)) {
+            metrics().set_queue_depth("storage_requests", self.storage_request_receiver.len());
+
+            let discriminant = mem::discriminant(&storage_request);
+            let executor = match self.executors.get(&discriminant) {
                 Some(executor) => executor,
                 None => {
                     info!(
@@ -55,7 +78,32 @@ impl<StorageRequestType: Debug> Dispatch<StorageRequestType> {
                 }
             };
 
-            if let Err(error) = executor(storage_request).await {
+            let variant_name = variant_names
+                .entry(discriminant)
+                .or_insert_with(|| variant_name(&storage_request))
+                .clone();
+            let span = match self
+                .span_extractor
+                .as_ref()
+                .and_then(|span_extractor| span_extractor(&storage_request))
+            {
+                Some(parent) => {
+                    info_span!(parent: &parent, "storage_request", request = %variant_name)
+                }
+                None => info_span!("storage_request", request = %variant_name),
+            };
+            let started_at = Instant::now();
+
+            let result = executor(storage_request).instrument(span).await;
+
+            metrics().record_executor(
+                "storage",
+                &variant_name,
+                result.is_err(),
+                started_at.elapsed(),
+            );
+
+            if let Err(error) = result {
                 info!("storage executor returned error: {}", &error);
             }
         }